@@ -29,9 +29,9 @@ fn main() {
     }
     // update and simulate
     system.set_button_state(button_state);
-    system.m_cycle();
+    system.run_frame();
     // change the title
-    let title = format!("LY: {}", system.mmio().ly());
+    let title = format!("Frame: {}", system.frame_count());
     win.set_title(&title);
     // draw
     let surface = sdl.create_surface_from(system.lcd(), 160, 144).unwrap();
@@ -62,19 +62,20 @@ impl ButtonState {
   }
   /// Converts the emulated button state into a `JOYP` value based on the two
   /// selection bits.
+  ///
+  /// Each low bit is 0 if a button on any selected row is held, and the unused
+  /// top two bits always read as 1.
   pub const fn to_joyp(self, action: bool, direction: bool) -> u8 {
-    let mut out = 0b1111;
+    let mut out = 0b1100_1111;
     // llvm plz be smarter than me and make this bit math fast
     if action {
-      out &= self.0 >> 4;
-    }
-    if direction {
-      out &= self.0;
-    }
-    if !action {
+      out &= 0b1111_0000 | self.0;
+    } else {
       out |= 1 << 5;
     }
-    if !direction {
+    if direction {
+      out &= 0b1111_0000 | (self.0 >> 4);
+    } else {
       out |= 1 << 4;
     }
     out
@@ -140,3 +141,12 @@ fn test_button_state() {
   let buttons = ButtonState::default().with_down(true);
   assert!(buttons.down());
 }
+
+#[test]
+fn test_to_joyp() {
+  let buttons = ButtonState::default().with_a(true).with_down(true);
+  assert_eq!(buttons.to_joyp(false, false), 0b1111_1111);
+  assert_eq!(buttons.to_joyp(true, false), 0b1101_1110);
+  assert_eq!(buttons.to_joyp(false, true), 0b1110_0111);
+  assert_eq!(buttons.to_joyp(true, true), 0b1100_0110);
+}
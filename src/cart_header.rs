@@ -0,0 +1,314 @@
+use crate::spare_parts::{ROM_BANK_SIZE, SRAM_BANK_SIZE};
+
+/// Everything the cartridge header says about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartHeader {
+  /// The game's title, up to 16 ASCII characters (fewer on newer carts).
+  pub title: String,
+  /// A 4 character code that only newer carts have.
+  pub manufacturer: Option<String>,
+  pub cgb: CgbFlag,
+  pub licensee: Licensee,
+  /// If the game supports the Super Game Boy.
+  pub sgb: bool,
+  pub cart_type: CartType,
+  /// The size of the ROM in bytes, if the size code is known.
+  pub rom_size: Option<usize>,
+  /// The size of the cart's RAM in bytes, if the size code is known. This
+  /// doesn't count RAM built into the mapper, like the MBC2's.
+  pub ram_size: Option<usize>,
+  /// If the game was sold in Japan (or overseas).
+  pub japanese: bool,
+  pub version: u8,
+  pub header_checksum: u8,
+  /// The boot rom won't start a game with a bad header checksum.
+  pub header_checksum_ok: bool,
+  pub global_checksum: u16,
+  /// Real hardware never checks this one.
+  pub global_checksum_ok: bool,
+}
+impl CartHeader {
+  pub fn parse(rom: &[u8]) -> Result<Self, ()> {
+    // check minimum length
+    if rom.len() < 0x150 {
+      return Err(());
+    }
+    let cgb = CgbFlag::from_byte(rom[0x143]);
+    // newer carts took the end of the title for the manufacturer code and the
+    // CGB flag.
+    let manufacturer_bytes = &rom[0x13F..0x143];
+    let has_manufacturer = cgb != CgbFlag::DmgOnly
+      && manufacturer_bytes.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+    let title_bytes = match (cgb, has_manufacturer) {
+      (_, true) => &rom[0x134..0x13F],
+      (CgbFlag::DmgOnly, false) => &rom[0x134..0x144],
+      (_, false) => &rom[0x134..0x143],
+    };
+    let title = ascii_string(title_bytes);
+    let manufacturer = has_manufacturer.then(|| ascii_string(manufacturer_bytes));
+    // the new licensee code is only used if the old one says so, and so is the
+    // SGB flag.
+    let licensee = match rom[0x14B] {
+      0x33 => Licensee::New([rom[0x144], rom[0x145]]),
+      old => Licensee::Old(old),
+    };
+    let sgb = rom[0x14B] == 0x33 && rom[0x146] == 0x03;
+    let header_checksum = rom[0x14D];
+    let computed_header_checksum =
+      rom[0x134..0x14D].iter().fold(0_u8, |x, b| x.wrapping_sub(*b).wrapping_sub(1));
+    let global_checksum = u16::from_be_bytes([rom[0x14E], rom[0x14F]]);
+    let computed_global_checksum = rom
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| !matches!(i, 0x14E | 0x14F))
+      .fold(0_u16, |x, (_, b)| x.wrapping_add(u16::from(*b)));
+    Ok(Self {
+      title,
+      manufacturer,
+      cgb,
+      licensee,
+      sgb,
+      cart_type: CartType::from_byte(rom[0x147]),
+      rom_size: rom_size(rom[0x148]),
+      ram_size: ram_size(rom[0x149]),
+      japanese: rom[0x14A] == 0x00,
+      version: rom[0x14C],
+      header_checksum,
+      header_checksum_ok: header_checksum == computed_header_checksum,
+      global_checksum,
+      global_checksum_ok: global_checksum == computed_global_checksum,
+    })
+  }
+}
+
+/// Turns header text into a `String`, stopping at the first null.
+fn ascii_string(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .take_while(|b| **b != 0)
+    .map(|b| if b.is_ascii_graphic() || *b == b' ' { char::from(*b) } else { '?' })
+    .collect()
+}
+
+/// Converts the ROM size code at `0x148` into a byte count.
+pub const fn rom_size(code: u8) -> Option<usize> {
+  match code {
+    0x00..=0x08 => Some((ROM_BANK_SIZE * 2) << code),
+    // a few unofficial codes some carts claim to use.
+    0x52 => Some(ROM_BANK_SIZE * 72),
+    0x53 => Some(ROM_BANK_SIZE * 80),
+    0x54 => Some(ROM_BANK_SIZE * 96),
+    _ => None,
+  }
+}
+
+/// Converts the RAM size code at `0x149` into a byte count.
+pub const fn ram_size(code: u8) -> Option<usize> {
+  match code {
+    0 => Some(0),
+    // this is supposed to be "unused", but sometimes meant 2k of ram.
+    1 => Some(2 * 1024),
+    2 => Some(SRAM_BANK_SIZE),
+    3 => Some(SRAM_BANK_SIZE * 4),
+    4 => Some(SRAM_BANK_SIZE * 16),
+    5 => Some(SRAM_BANK_SIZE * 8),
+    _ => None,
+  }
+}
+
+/// How a game uses the CGB, from `0x143`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbFlag {
+  DmgOnly,
+  /// Works on the DMG, with extras on the CGB.
+  CgbEnhanced,
+  CgbOnly,
+}
+impl CgbFlag {
+  #[inline]
+  pub const fn from_byte(byte: u8) -> Self {
+    match byte {
+      0xC0 => Self::CgbOnly,
+      // the CGB only really looks at bit 7.
+      b if b & 0x80 != 0 => Self::CgbEnhanced,
+      _ => Self::DmgOnly,
+    }
+  }
+}
+
+/// Who published the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Licensee {
+  /// The code at `0x14B`.
+  Old(u8),
+  /// Two ASCII characters at `0x144`, used when the old code is `0x33`.
+  New([u8; 2]),
+}
+
+/// The mapper and other hardware in the cart, from `0x147`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartType {
+  RomOnly,
+  Mbc1,
+  Mbc1Ram,
+  Mbc1RamBattery,
+  Mbc2,
+  Mbc2Battery,
+  RomRam,
+  RomRamBattery,
+  Mmm01,
+  Mmm01Ram,
+  Mmm01RamBattery,
+  Mbc3TimerBattery,
+  Mbc3TimerRamBattery,
+  Mbc3,
+  Mbc3Ram,
+  Mbc3RamBattery,
+  Mbc5,
+  Mbc5Ram,
+  Mbc5RamBattery,
+  Mbc5Rumble,
+  Mbc5RumbleRam,
+  Mbc5RumbleRamBattery,
+  Mbc6,
+  Mbc7SensorRumbleRamBattery,
+  PocketCamera,
+  BandaiTama5,
+  HuC3,
+  HuC1RamBattery,
+  Unknown(u8),
+}
+impl CartType {
+  pub const fn from_byte(byte: u8) -> Self {
+    match byte {
+      0x00 => Self::RomOnly,
+      0x01 => Self::Mbc1,
+      0x02 => Self::Mbc1Ram,
+      0x03 => Self::Mbc1RamBattery,
+      0x05 => Self::Mbc2,
+      0x06 => Self::Mbc2Battery,
+      0x08 => Self::RomRam,
+      0x09 => Self::RomRamBattery,
+      0x0B => Self::Mmm01,
+      0x0C => Self::Mmm01Ram,
+      0x0D => Self::Mmm01RamBattery,
+      0x0F => Self::Mbc3TimerBattery,
+      0x10 => Self::Mbc3TimerRamBattery,
+      0x11 => Self::Mbc3,
+      0x12 => Self::Mbc3Ram,
+      0x13 => Self::Mbc3RamBattery,
+      0x19 => Self::Mbc5,
+      0x1A => Self::Mbc5Ram,
+      0x1B => Self::Mbc5RamBattery,
+      0x1C => Self::Mbc5Rumble,
+      0x1D => Self::Mbc5RumbleRam,
+      0x1E => Self::Mbc5RumbleRamBattery,
+      0x20 => Self::Mbc6,
+      0x22 => Self::Mbc7SensorRumbleRamBattery,
+      0xFC => Self::PocketCamera,
+      0xFD => Self::BandaiTama5,
+      0xFE => Self::HuC3,
+      0xFF => Self::HuC1RamBattery,
+      other => Self::Unknown(other),
+    }
+  }
+  /// If the cart's RAM (or clock) keeps its data with the power off.
+  #[inline]
+  pub const fn has_battery(self) -> bool {
+    matches!(
+      self,
+      Self::Mbc1RamBattery
+        | Self::Mbc2Battery
+        | Self::RomRamBattery
+        | Self::Mmm01RamBattery
+        | Self::Mbc3TimerBattery
+        | Self::Mbc3TimerRamBattery
+        | Self::Mbc3RamBattery
+        | Self::Mbc5RamBattery
+        | Self::Mbc5RumbleRamBattery
+        | Self::Mbc7SensorRumbleRamBattery
+        | Self::HuC1RamBattery
+    )
+  }
+  #[inline]
+  pub const fn has_timer(self) -> bool {
+    matches!(self, Self::Mbc3TimerBattery | Self::Mbc3TimerRamBattery)
+  }
+  #[inline]
+  pub const fn has_rumble(self) -> bool {
+    matches!(
+      self,
+      Self::Mbc5Rumble
+        | Self::Mbc5RumbleRam
+        | Self::Mbc5RumbleRamBattery
+        | Self::Mbc7SensorRumbleRamBattery
+    )
+  }
+}
+
+#[test]
+fn test_cart_header() {
+  fn make_rom(title: &[u8], manufacturer: &[u8], cgb: u8, old_licensee: u8) -> Vec<u8> {
+    let mut rom = vec![0_u8; ROM_BANK_SIZE * 2];
+    rom[0x134..0x134 + title.len()].copy_from_slice(title);
+    rom[0x13F..0x13F + manufacturer.len()].copy_from_slice(manufacturer);
+    rom[0x143] = cgb;
+    rom[0x144..0x146].copy_from_slice(b"01");
+    rom[0x146] = 0x03;
+    rom[0x147] = 0x03;
+    rom[0x14B] = old_licensee;
+    rom[0x14D] = rom[0x134..0x14D].iter().fold(0_u8, |x, b| x.wrapping_sub(*b).wrapping_sub(1));
+    let global = rom.iter().fold(0_u16, |x, b| x.wrapping_add(u16::from(*b)));
+    rom[0x14E..0x150].copy_from_slice(&global.to_be_bytes());
+    rom
+  }
+
+  // on older carts the last title character is where the CGB flag goes now.
+  let rom = make_rom(b"DMG TITLE FULL!!", b"", b'!', 0x01);
+  let header = CartHeader::parse(&rom).unwrap();
+  assert_eq!(header.title, "DMG TITLE FULL!!");
+  assert_eq!(header.manufacturer, None);
+  assert_eq!(header.cgb, CgbFlag::DmgOnly);
+  assert_eq!(header.cart_type, CartType::Mbc1RamBattery);
+  assert_eq!(header.licensee, Licensee::Old(0x01));
+  // the sgb flag only counts with the new licensee code.
+  assert!(!header.sgb);
+  assert!(header.header_checksum_ok);
+  assert!(header.global_checksum_ok);
+
+  let rom = make_rom(b"SHORT", b"", 0x80, 0x33);
+  let header = CartHeader::parse(&rom).unwrap();
+  assert_eq!(header.title, "SHORT");
+  assert_eq!(header.manufacturer, None);
+  assert_eq!(header.cgb, CgbFlag::CgbEnhanced);
+  assert_eq!(header.licensee, Licensee::New(*b"01"));
+  assert!(header.sgb);
+
+  let mut rom = make_rom(b"CGB TITLE", b"ABCD", 0xC0, 0x33);
+  let header = CartHeader::parse(&rom).unwrap();
+  assert_eq!(header.title, "CGB TITLE");
+  assert_eq!(header.manufacturer.as_deref(), Some("ABCD"));
+  assert_eq!(header.cgb, CgbFlag::CgbOnly);
+
+  // changing the program only breaks the global checksum.
+  rom[0x150] = 0xAA;
+  let header = CartHeader::parse(&rom).unwrap();
+  assert!(header.header_checksum_ok);
+  assert!(!header.global_checksum_ok);
+  rom[0x134] = b'X';
+  let header = CartHeader::parse(&rom).unwrap();
+  assert!(!header.header_checksum_ok);
+  assert!(!header.global_checksum_ok);
+
+  assert!(CartHeader::parse(&rom[..0x14F]).is_err());
+  assert_eq!(rom_size(0x00), Some(32 * 1024));
+  assert_eq!(rom_size(0x08), Some(8 * 1024 * 1024));
+  assert_eq!(rom_size(0x09), None);
+  assert_eq!(rom_size(0x52), Some(1152 * 1024));
+  assert_eq!(rom_size(0x53), Some(1280 * 1024));
+  assert_eq!(rom_size(0x54), Some(1536 * 1024));
+  assert_eq!(ram_size(0x01), Some(2 * 1024));
+  assert_eq!(ram_size(0x04), Some(128 * 1024));
+  assert_eq!(ram_size(0x05), Some(64 * 1024));
+  assert_eq!(ram_size(0x06), None);
+}
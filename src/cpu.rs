@@ -13,6 +13,20 @@ pub trait CpuView {
     let if_ = self.read(0xFF0F);
     self.write(0xFF0F, if_ ^ bit);
   }
+
+  /// Called after the CPU reads an address, for buses where reading has side
+  /// effects.
+  fn after_read(&mut self, _address: u16) {}
+  /// Called when a 16-bit increment or decrement puts an address on the bus
+  /// without reading or writing it.
+  fn idu_address(&mut self, _address: u16) {}
+  /// Called once per M-cycle, for things with their own clock (such as a
+  /// cart's RTC).
+  fn m_cycle(&mut self) {}
+  /// If there's a rumble motor, and it's running right now.
+  fn rumble(&self) -> bool {
+    false
+  }
 }
 impl CpuView for Vec<u8> {
   #[inline]
@@ -309,6 +323,7 @@ impl SM83 {
       Read(r8, r16, i) => {
         let address = self.get_r16(r16);
         let byte = bus.read(address);
+        bus.after_read(address);
         self.set_r8(r8, byte);
         let new_address = address.wrapping_add(i16::from(i) as u16);
         self.set_r16(r16, new_address);
@@ -322,6 +337,7 @@ impl SM83 {
       }
       Delta16(r16, i) => {
         let r = self.get_r16(r16);
+        bus.idu_address(r);
         let new_r = r.wrapping_add(i16::from(i) as u16);
         self.set_r16(r16, new_r);
       }
@@ -492,6 +508,7 @@ impl SM83 {
       Call(cond) => {
         if self.is_cond(cond) {
           // magical simultaneous SP adjustment when the condition holds!
+          bus.idu_address(self.sp());
           self.set_sp(self.sp().wrapping_sub(1));
           self.queue.extend([
             // rustfmt don't make this a one-liner
@@ -518,7 +535,9 @@ impl SM83 {
           5 => self.l(),
           6 => {
             self.queue.extend([Nop]);
-            bus.read(self.hl())
+            let byte = bus.read(self.hl());
+            bus.after_read(self.hl());
+            byte
           }
           7 => self.a(),
           _ => unimplemented!(),
@@ -709,6 +728,7 @@ impl SM83 {
   fn crank_pc(&mut self, bus: &mut impl CpuView) {
     let address = self.pc;
     let op = bus.read(address);
+    bus.after_read(address);
     let actions = OP_TABLE[usize::from(op)];
     debug_assert!(!actions.is_empty());
     self.queue.extend(actions.iter().copied());
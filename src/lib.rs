@@ -9,9 +9,18 @@ use cpu::{CpuMode, CpuView, SM83};
 use spare_parts::SpareParts;
 
 pub mod button_state;
+pub mod cart_header;
 pub mod cpu;
+pub mod link;
 pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
 pub mod mmio;
+pub mod net_link;
 pub mod ppu;
+pub mod printer;
+pub mod rom_only;
+pub mod serial;
 pub mod spare_parts;
 pub mod system;
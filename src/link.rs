@@ -0,0 +1,151 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{serial::SerialDevice, system::System};
+
+/// The shared state of a link cable between two ports.
+#[derive(Debug, Default)]
+struct LinkWire {
+  /// The bit each side is currently putting on the wire.
+  bit_out: [bool; 2],
+  /// Clock edges sent to each side.
+  clock_edge: [Option<ClockEdge>; 2],
+}
+
+/// A clock edge sent along the wire, along with the bit sent.
+#[derive(Debug, Clone, Copy)]
+struct ClockEdge {
+  bit: bool,
+  /// If the receiving side has had an M-cycle to use the edge already.
+  seen: bool,
+}
+
+/// One end of an in-process link cable.
+///
+/// Whichever side's `SC` selects the internal clock drives the transfer, and
+/// the other side (selecting the external clock) follows along.
+#[derive(Debug)]
+pub struct LinkPort {
+  wire: Rc<RefCell<LinkWire>>,
+  side: usize,
+}
+impl LinkPort {
+  /// Makes both ends of a new cable.
+  pub fn pair() -> (Self, Self) {
+    let wire = Rc::new(RefCell::new(LinkWire { bit_out: [true; 2], ..Default::default() }));
+    (Self { wire: wire.clone(), side: 0 }, Self { wire, side: 1 })
+  }
+  #[inline]
+  fn other_side(&self) -> usize {
+    1 - self.side
+  }
+}
+impl SerialDevice for LinkPort {
+  #[inline]
+  fn exchange_bit(&mut self, bit_out: bool) -> bool {
+    let mut wire = self.wire.borrow_mut();
+    let other = self.other_side();
+    wire.clock_edge[other] = Some(ClockEdge { bit: bit_out, seen: false });
+    wire.bit_out[self.side] = bit_out;
+    wire.bit_out[other]
+  }
+  #[inline]
+  fn external_clock(&mut self, bit_out: bool) -> Option<bool> {
+    let mut wire = self.wire.borrow_mut();
+    wire.bit_out[self.side] = bit_out;
+    wire.clock_edge[self.side].take().map(|edge| edge.bit)
+  }
+  #[inline]
+  fn m_cycle(&mut self, _transfer: bool) {
+    // edges only count if this side was waiting for them right away, which
+    // gives either side a chance to go first each M-cycle.
+    let mut wire = self.wire.borrow_mut();
+    let edge = &mut wire.clock_edge[self.side];
+    match edge {
+      Some(ClockEdge { seen: true, .. }) => *edge = None,
+      Some(ClockEdge { seen, .. }) => *seen = true,
+      None => (),
+    }
+  }
+}
+
+/// Two systems connected by a link cable, stepped in lockstep.
+pub struct LinkedSystems {
+  pub left: System,
+  pub right: System,
+}
+impl LinkedSystems {
+  /// Connects the systems, replacing any serial devices they had.
+  pub fn new(mut left: System, mut right: System) -> Self {
+    let (left_port, right_port) = LinkPort::pair();
+    left.set_serial_device(Box::new(left_port));
+    right.set_serial_device(Box::new(right_port));
+    Self { left, right }
+  }
+
+  #[inline]
+  pub fn t_cycle(&mut self) {
+    self.left.t_cycle();
+    self.right.t_cycle();
+  }
+
+  #[inline]
+  pub fn m_cycle(&mut self) {
+    self.t_cycle();
+    self.t_cycle();
+    self.t_cycle();
+    self.t_cycle();
+  }
+
+  /// Runs until the left system's current frame is done.
+  #[inline]
+  pub fn run_frame(&mut self) {
+    let frame_count = self.left.frame_count();
+    while self.left.frame_count() == frame_count {
+      self.t_cycle();
+    }
+  }
+}
+
+#[test]
+fn test_link_port_transfer() {
+  use crate::{mmio::MMIO, spare_parts::IrqTy};
+
+  let (left_port, right_port) = LinkPort::pair();
+  let mut left = MMIO::default();
+  let mut right = MMIO::default();
+  left.set_serial_device(Box::new(left_port));
+  right.set_serial_device(Box::new(right_port));
+  left.write(MMIO::SB as u8, 0x12);
+  right.write(MMIO::SB as u8, 0x34);
+  // the right side waits on the left side's clock.
+  right.write(MMIO::SC as u8, 0x80);
+  left.write(MMIO::SC as u8, 0x81);
+  // 8 bits at 8192 Hz, plus a little extra for however the clock lines up.
+  for _ in 0..(8 * 128 + 128) {
+    left.m_cycle();
+    right.m_cycle();
+  }
+  assert_eq!(left.sb(), 0x34);
+  assert_eq!(right.sb(), 0x12);
+  assert_eq!(left.sc() & 0x80, 0);
+  assert_eq!(right.sc() & 0x80, 0);
+  assert_ne!(left.if_() & (1 << IrqTy::Serial as u8), 0);
+  assert_ne!(right.if_() & (1 << IrqTy::Serial as u8), 0);
+
+  // clocking out a byte while the right side isn't listening doesn't leave
+  // any edges behind for its next transfer.
+  left.write(MMIO::SB as u8, 0x56);
+  left.write(MMIO::SC as u8, 0x81);
+  for _ in 0..(8 * 128 + 128) {
+    left.m_cycle();
+    right.m_cycle();
+  }
+  right.write(MMIO::SB as u8, 0xAA);
+  right.write(MMIO::SC as u8, 0x80);
+  for _ in 0..256 {
+    left.m_cycle();
+    right.m_cycle();
+  }
+  assert_eq!(right.sb(), 0xAA);
+  assert_eq!(right.sc() & 0x80, 0x80);
+}
@@ -1,4 +1,5 @@
 use crate::{
+  cart_header::{CartHeader, CartType},
   cpu::CpuView,
   spare_parts::{ROM_BANK_SIZE, SRAM_BANK_SIZE},
 };
@@ -17,33 +18,25 @@ pub struct MBC1 {
 }
 impl MBC1 {
   pub fn new(raw_rom: &[u8], raw_ram: Option<&[u8]>) -> Result<Self, ()> {
-    // check minimum length
-    if raw_rom.len() < 0x150 {
-      return Err(());
-    }
-    // check for correct cart type byte.
-    match raw_rom[0x147] {
-      1 | 2 | 3 => (),
+    let header = CartHeader::parse(raw_rom)?;
+    // check for correct cart type.
+    match header.cart_type {
+      CartType::Mbc1 | CartType::Mbc1Ram | CartType::Mbc1RamBattery => (),
       _ => return Err(()),
     }
-    // determine bank counts
-    let rom_bank_count = (1 << raw_rom[0x148]).max(2);
-    if rom_bank_count == 0 {
+    // determine bank counts, the MBC1 can only select up to 128 ROM banks.
+    let rom_bank_count = header.rom_size.ok_or(())? / ROM_BANK_SIZE;
+    if rom_bank_count > 128 {
       return Err(());
     }
-    let ram_bank_count = match raw_rom[0x149] {
-      // technically this is "no ram" but we fudge it a bit and always give at
-      // least 1 ram bank so that computations elsewhere don't have to worry
-      // about the len=0 case.
+    let ram_bank_count = match header.ram_size.ok_or(())? / SRAM_BANK_SIZE {
+      // technically "no ram" (or 2k of ram) is less than a bank, but we fudge
+      // it a bit and always give at least 1 ram bank so that computations
+      // elsewhere don't have to worry about the len=0 case.
       0 => 1,
-      // this is supposed to be "unused", but sometimes meant half a bank of
-      // ram, so whatever we'll just give 1 bank.
-      1 => 1,
       // these are normal values
-      2 => 1,
-      3 => 4,
-      // 5 is a legal value for some mappers, but not for MBC1. Any other values
-      // are not expected.
+      count @ (1 | 4) => count,
+      // more banks are legal for some mappers, but not for MBC1.
       _ => return Err(()),
     };
     // copy input data to banks
@@ -144,3 +137,19 @@ impl CpuView for MBC1 {
     }
   }
 }
+
+#[test]
+fn test_mbc1_rom_size_limit() {
+  let mut raw_rom = vec![0_u8; ROM_BANK_SIZE * 128];
+  raw_rom[0x147] = 1;
+  raw_rom[0x148] = 6;
+  raw_rom[ROM_BANK_SIZE * 127] = 127;
+  let mut cart = MBC1::new(&raw_rom, None).unwrap();
+  cart.write(0x2000, 0x1F);
+  cart.write(0x4000, 0x3);
+  assert_eq!(cart.read(0x4000), 127);
+
+  raw_rom[0x148] = 7;
+  raw_rom.resize(ROM_BANK_SIZE * 256, 0);
+  assert!(MBC1::new(&raw_rom, None).is_err());
+}
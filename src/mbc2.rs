@@ -0,0 +1,116 @@
+use crate::{
+  cart_header::{CartHeader, CartType},
+  cpu::CpuView,
+  spare_parts::ROM_BANK_SIZE,
+};
+
+/// The MBC2 has 512 half-bytes of RAM built in.
+pub const MBC2_RAM_SIZE: usize = 512;
+
+pub struct MBC2 {
+  rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+  /// Only the low 4 bits of each byte are used.
+  ram: [u8; MBC2_RAM_SIZE],
+  ram_active: bool,
+  rom_x_index: usize,
+}
+impl MBC2 {
+  pub fn new(raw_rom: &[u8], raw_ram: Option<&[u8]>) -> Result<Self, ()> {
+    let header = CartHeader::parse(raw_rom)?;
+    // check for correct cart type.
+    match header.cart_type {
+      CartType::Mbc2 | CartType::Mbc2Battery => (),
+      _ => return Err(()),
+    }
+    // determine bank count, the MBC2 can only select up to 16 banks.
+    let rom_bank_count = header.rom_size.ok_or(())? / ROM_BANK_SIZE;
+    if rom_bank_count > 16 {
+      return Err(());
+    }
+    // copy input data to banks
+    let mut rom_banks = vec![[0u8; ROM_BANK_SIZE]; rom_bank_count];
+    rom_banks
+      .iter_mut()
+      .zip(raw_rom.chunks(ROM_BANK_SIZE))
+      .for_each(|(b, r)| b[..r.len()].copy_from_slice(r));
+    let mut ram = [0_u8; MBC2_RAM_SIZE];
+    ram.iter_mut().zip(raw_ram.unwrap_or(&[])).for_each(|(b, r)| *b = r & 0xF);
+    // complete
+    Ok(Self { rom_banks, ram, ram_active: false, rom_x_index: 1 })
+  }
+}
+impl CpuView for MBC2 {
+  #[inline]
+  fn read(&self, address: u16) -> u8 {
+    match address {
+      0x0000..=0x3FFF => {
+        let index = usize::from(address);
+        self.rom_banks[0][index]
+      }
+      0x4000..=0x7FFF => {
+        let bank = &self.rom_banks[self.rom_x_index];
+        let index = usize::from(address) - 0x4000;
+        bank[index]
+      }
+      // the ram repeats across the whole area, and the upper bits of each
+      // byte are open bus.
+      0xA000..=0xBFFF if self.ram_active => {
+        let index = usize::from(address) % MBC2_RAM_SIZE;
+        0xF0 | self.ram[index]
+      }
+      _ => 0xFF,
+    }
+  }
+  #[inline]
+  fn write(&mut self, address: u16, byte: u8) {
+    match address {
+      // address bit 8 picks between the two registers.
+      0x0000..=0x3FFF if address & 0x100 == 0 => self.ram_active = (byte & 0xF) == 0xA,
+      0x0000..=0x3FFF => {
+        let bank = usize::from(byte & 0xF).max(1);
+        self.rom_x_index = bank % self.rom_banks.len();
+      }
+      0xA000..=0xBFFF if self.ram_active => {
+        let index = usize::from(address) % MBC2_RAM_SIZE;
+        self.ram[index] = byte & 0xF;
+      }
+      _ => (),
+    }
+  }
+}
+
+#[test]
+fn test_mbc2() {
+  let mut raw_rom = vec![0_u8; ROM_BANK_SIZE * 4];
+  raw_rom[0x147] = 5;
+  raw_rom[0x148] = 1;
+  for (i, bank) in raw_rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+    bank[0] = i as u8;
+  }
+  let mut cart = MBC2::new(&raw_rom, None).unwrap();
+
+  // address bit 8 set selects the ROM bank.
+  assert_eq!(cart.read(0x4000), 1);
+  cart.write(0x0100, 2);
+  assert_eq!(cart.read(0x4000), 2);
+  cart.write(0x3FFF, 0xF3);
+  assert_eq!(cart.read(0x4000), 3);
+  cart.write(0x2100, 0);
+  assert_eq!(cart.read(0x4000), 1);
+  // address bit 8 clear is the RAM enable, and doesn't touch the ROM bank.
+  cart.write(0x0000, 2);
+  assert_eq!(cart.read(0x4000), 1);
+  assert_eq!(cart.read(0xA000), 0xFF);
+  cart.write(0x3EFF, 0x0A);
+
+  // 4 bit values repeating every 512 bytes.
+  cart.write(0xA000, 0x5C);
+  assert_eq!(cart.read(0xA000), 0xFC);
+  assert_eq!(cart.read(0xA200), 0xFC);
+  assert_eq!(cart.read(0xBE00), 0xFC);
+  cart.write(0xBFFF, 0x03);
+  assert_eq!(cart.read(0xA1FF), 0xF3);
+
+  cart.write(0x0000, 0);
+  assert_eq!(cart.read(0xA000), 0xFF);
+}
@@ -0,0 +1,236 @@
+use crate::{
+  cart_header::{CartHeader, CartType},
+  cpu::CpuView,
+  spare_parts::{ROM_BANK_SIZE, SRAM_BANK_SIZE},
+};
+
+pub struct MBC3 {
+  rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+  ram_banks: Vec<[u8; SRAM_BANK_SIZE]>,
+  /// Carts without a clock don't get one.
+  rtc: Option<Rtc>,
+  ram_active: bool,
+  rom_x_index: usize,
+  /// `0x00..=0x03` select a RAM bank, and `0x08..=0x0C` select an RTC
+  /// register.
+  ram_select: u8,
+  /// The last byte written to the latch register.
+  latch_byte: u8,
+}
+impl MBC3 {
+  pub fn new(raw_rom: &[u8], raw_ram: Option<&[u8]>) -> Result<Self, ()> {
+    let header = CartHeader::parse(raw_rom)?;
+    // check for correct cart type.
+    match header.cart_type {
+      CartType::Mbc3TimerBattery
+      | CartType::Mbc3TimerRamBattery
+      | CartType::Mbc3
+      | CartType::Mbc3Ram
+      | CartType::Mbc3RamBattery => (),
+      _ => return Err(()),
+    }
+    // determine bank counts
+    let rom_bank_count = header.rom_size.ok_or(())? / ROM_BANK_SIZE;
+    if rom_bank_count > 128 {
+      return Err(());
+    }
+    let ram_bank_count = match header.ram_size.ok_or(())? / SRAM_BANK_SIZE {
+      // like with the MBC1, less than a bank of ram still gets 1 bank so that
+      // computations elsewhere don't have to worry about the len=0 case.
+      0 => 1,
+      count @ (1 | 4) => count,
+      _ => return Err(()),
+    };
+    // copy input data to banks
+    let mut rom_banks = vec![[0u8; ROM_BANK_SIZE]; rom_bank_count];
+    rom_banks
+      .iter_mut()
+      .zip(raw_rom.chunks(ROM_BANK_SIZE))
+      .for_each(|(b, r)| b[..r.len()].copy_from_slice(r));
+    let mut ram_banks = vec![[0u8; SRAM_BANK_SIZE]; ram_bank_count];
+    ram_banks
+      .iter_mut()
+      .zip(raw_ram.unwrap_or(&[]).chunks(SRAM_BANK_SIZE))
+      .for_each(|(b, r)| b[..r.len()].copy_from_slice(r));
+    // complete
+    Ok(Self {
+      rom_banks,
+      ram_banks,
+      rtc: if header.cart_type.has_timer() { Some(Rtc::default()) } else { None },
+      ram_active: false,
+      rom_x_index: 1,
+      ram_select: 0,
+      latch_byte: 0xFF,
+    })
+  }
+
+  /// The clock's registers (seconds, minutes, hours, day low, day high) as
+  /// they're counting right now, if the cart has a clock.
+  #[inline]
+  pub fn rtc_registers(&self) -> Option<[u8; 5]> {
+    self.rtc.as_ref().map(|rtc| rtc.live)
+  }
+  /// Sets the clock, such as when loading a save.
+  #[inline]
+  pub fn set_rtc_registers(&mut self, registers: [u8; 5]) {
+    if let Some(rtc) = &mut self.rtc {
+      for (i, byte) in registers.into_iter().enumerate() {
+        rtc.write(i, byte);
+      }
+    }
+  }
+}
+impl CpuView for MBC3 {
+  #[inline]
+  fn read(&self, address: u16) -> u8 {
+    match address {
+      0x0000..=0x3FFF => {
+        let index = usize::from(address);
+        self.rom_banks[0][index]
+      }
+      0x4000..=0x7FFF => {
+        let bank = &self.rom_banks[self.rom_x_index];
+        let index = usize::from(address) - 0x4000;
+        bank[index]
+      }
+      0xA000..=0xBFFF if self.ram_active => match (self.ram_select, &self.rtc) {
+        (0x00..=0x03, _) => {
+          let bank = &self.ram_banks[usize::from(self.ram_select) % self.ram_banks.len()];
+          let index = usize::from(address) - 0xA000;
+          bank[index]
+        }
+        (0x08..=0x0C, Some(rtc)) => rtc.latched[usize::from(self.ram_select - 0x08)],
+        _ => 0xFF,
+      },
+      _ => 0xFF,
+    }
+  }
+  #[inline]
+  fn write(&mut self, address: u16, byte: u8) {
+    match address {
+      0x0000..=0x1FFF => self.ram_active = (byte & 0xF) == 0xA,
+      0x2000..=0x3FFF => {
+        let bank = usize::from(byte & 0x7F).max(1);
+        self.rom_x_index = bank % self.rom_banks.len();
+      }
+      0x4000..=0x5FFF => self.ram_select = byte,
+      0x6000..=0x7FFF => {
+        // writing 0 and then 1 copies the clock into the readable registers.
+        if self.latch_byte == 0x00 && byte == 0x01 {
+          if let Some(rtc) = &mut self.rtc {
+            rtc.latched = rtc.live;
+          }
+        }
+        self.latch_byte = byte;
+      }
+      0xA000..=0xBFFF if self.ram_active => match (self.ram_select, &mut self.rtc) {
+        (0x00..=0x03, _) => {
+          let bank_count = self.ram_banks.len();
+          let bank = &mut self.ram_banks[usize::from(self.ram_select) % bank_count];
+          let index = usize::from(address) - 0xA000;
+          bank[index] = byte;
+        }
+        (0x08..=0x0C, Some(rtc)) => rtc.write(usize::from(self.ram_select - 0x08), byte),
+        _ => (),
+      },
+      _ => (),
+    }
+  }
+  #[inline]
+  fn m_cycle(&mut self) {
+    if let Some(rtc) = &mut self.rtc {
+      rtc.m_cycle();
+    }
+  }
+}
+
+/// The MBC3's real time clock, which counts along with the emulated time (not
+/// the host's time).
+#[derive(Debug, Clone, Copy, Default)]
+struct Rtc {
+  /// seconds, minutes, hours, day low, day high
+  live: [u8; 5],
+  /// What the CPU sees, as of the last latch.
+  latched: [u8; 5],
+  /// M-cycles since the last second ticked.
+  sub_cycles: u32,
+}
+impl Rtc {
+  const SECONDS: usize = 0;
+  const MINUTES: usize = 1;
+  const HOURS: usize = 2;
+  const DAY_LOW: usize = 3;
+  const DAY_HIGH: usize = 4;
+  /// The bits of each register that exist.
+  const MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+  const CYCLES_PER_SECOND: u32 = 1024 * 1024;
+
+  #[inline]
+  fn halted(&self) -> bool {
+    self.live[Self::DAY_HIGH] & 0x40 != 0
+  }
+  #[inline]
+  fn write(&mut self, register: usize, byte: u8) {
+    self.live[register] = byte & Self::MASKS[register];
+    // writing the seconds also restarts the current second.
+    if register == Self::SECONDS {
+      self.sub_cycles = 0;
+    }
+  }
+  #[inline]
+  fn m_cycle(&mut self) {
+    if self.halted() {
+      return;
+    }
+    self.sub_cycles += 1;
+    if self.sub_cycles == Self::CYCLES_PER_SECOND {
+      self.sub_cycles = 0;
+      self.tick_second();
+    }
+  }
+  /// Counts up one second. Values that were set out of range keep counting up
+  /// until they wrap around on their own, without carrying.
+  fn tick_second(&mut self) {
+    let live = &mut self.live;
+    for (register, limit) in [(Self::SECONDS, 60), (Self::MINUTES, 60), (Self::HOURS, 24)] {
+      live[register] = (live[register] + 1) & Self::MASKS[register];
+      if live[register] != limit {
+        return;
+      }
+      live[register] = 0;
+    }
+    let day = u16::from_le_bytes([live[Self::DAY_LOW], live[Self::DAY_HIGH] & 1]) + 1;
+    let [day_low, day_high] = day.to_le_bytes();
+    live[Self::DAY_LOW] = day_low;
+    live[Self::DAY_HIGH] = (live[Self::DAY_HIGH] & 0xFE) | (day_high & 1);
+    // the day counter overflowing sets the carry bit, which stays set until
+    // it's cleared.
+    if day_high & 0b10 != 0 {
+      live[Self::DAY_HIGH] |= 0x80;
+    }
+  }
+}
+
+#[test]
+fn test_mbc3_rtc() {
+  let mut rom = vec![0_u8; ROM_BANK_SIZE * 2];
+  rom[0x147] = 0x10;
+  let mut mbc3 = MBC3::new(&rom, None).unwrap();
+  mbc3.write(0x0000, 0x0A);
+  mbc3.set_rtc_registers([59, 59, 23, 0xFF, 0x01]);
+  for _ in 0..Rtc::CYCLES_PER_SECOND {
+    mbc3.m_cycle();
+  }
+  // nothing shows up until the clock is latched.
+  mbc3.write(0x4000, 0x08);
+  assert_eq!(mbc3.read(0xA000), 0);
+  mbc3.write(0x6000, 0x00);
+  mbc3.write(0x6000, 0x01);
+  let latched: Vec<u8> = (0x08..=0x0C)
+    .map(|register| {
+      mbc3.write(0x4000, register);
+      mbc3.read(0xA000)
+    })
+    .collect();
+  assert_eq!(latched, [0, 0, 0, 0, 0x80]);
+}
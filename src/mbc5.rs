@@ -0,0 +1,157 @@
+use crate::{
+  cart_header::{CartHeader, CartType},
+  cpu::CpuView,
+  spare_parts::{ROM_BANK_SIZE, SRAM_BANK_SIZE},
+};
+
+pub struct MBC5 {
+  rom_banks: Vec<[u8; ROM_BANK_SIZE]>,
+  ram_banks: Vec<[u8; SRAM_BANK_SIZE]>,
+  /// Rumble carts use bit 3 of the RAM bank for the motor instead.
+  has_rumble: bool,
+  rumble: bool,
+  ram_active: bool,
+  low8_bits: u8,
+  bit9: bool,
+  // these are cached values that the `update_indexes` method regenerates.
+  rom_x_index: usize,
+  ram_index: usize,
+}
+impl MBC5 {
+  pub fn new(raw_rom: &[u8], raw_ram: Option<&[u8]>) -> Result<Self, ()> {
+    let header = CartHeader::parse(raw_rom)?;
+    // check for correct cart type.
+    match header.cart_type {
+      CartType::Mbc5
+      | CartType::Mbc5Ram
+      | CartType::Mbc5RamBattery
+      | CartType::Mbc5Rumble
+      | CartType::Mbc5RumbleRam
+      | CartType::Mbc5RumbleRamBattery => (),
+      _ => return Err(()),
+    }
+    // determine bank counts
+    let rom_bank_count = header.rom_size.ok_or(())? / ROM_BANK_SIZE;
+    if rom_bank_count > 512 {
+      return Err(());
+    }
+    // like with the MBC1, less than a bank of ram still gets 1 bank so that
+    // computations elsewhere don't have to worry about the len=0 case.
+    let ram_bank_count = (header.ram_size.ok_or(())? / SRAM_BANK_SIZE).max(1);
+    // copy input data to banks
+    let mut rom_banks = vec![[0u8; ROM_BANK_SIZE]; rom_bank_count];
+    rom_banks
+      .iter_mut()
+      .zip(raw_rom.chunks(ROM_BANK_SIZE))
+      .for_each(|(b, r)| b[..r.len()].copy_from_slice(r));
+    let mut ram_banks = vec![[0u8; SRAM_BANK_SIZE]; ram_bank_count];
+    ram_banks
+      .iter_mut()
+      .zip(raw_ram.unwrap_or(&[]).chunks(SRAM_BANK_SIZE))
+      .for_each(|(b, r)| b[..r.len()].copy_from_slice(r));
+    // complete
+    Ok(Self {
+      rom_banks,
+      ram_banks,
+      has_rumble: header.cart_type.has_rumble(),
+      rumble: false,
+      ram_active: false,
+      low8_bits: 1,
+      bit9: false,
+      rom_x_index: 1,
+      ram_index: 0,
+    })
+  }
+
+  fn update_indexes(&mut self) {
+    // unlike the MBC1, bank 0 can be picked for the upper rom area.
+    let full_index = usize::from(self.bit9) << 8 | usize::from(self.low8_bits);
+    self.rom_x_index = full_index % self.rom_banks.len();
+  }
+}
+impl CpuView for MBC5 {
+  #[inline]
+  fn read(&self, address: u16) -> u8 {
+    match address {
+      0x0000..=0x3FFF => {
+        let index = usize::from(address);
+        self.rom_banks[0][index]
+      }
+      0x4000..=0x7FFF => {
+        let bank = &self.rom_banks[self.rom_x_index];
+        let index = usize::from(address) - 0x4000;
+        bank[index]
+      }
+      0xA000..=0xBFFF if self.ram_active => {
+        let bank = &self.ram_banks[self.ram_index];
+        let index = usize::from(address) - 0xA000;
+        bank[index]
+      }
+      _ => 0xFF,
+    }
+  }
+  #[inline]
+  fn write(&mut self, address: u16, byte: u8) {
+    match address {
+      0x0000..=0x1FFF => self.ram_active = (byte & 0xF) == 0xA,
+      0x2000..=0x2FFF => {
+        self.low8_bits = byte;
+        self.update_indexes();
+      }
+      0x3000..=0x3FFF => {
+        self.bit9 = (byte & 0b1) != 0;
+        self.update_indexes();
+      }
+      0x4000..=0x5FFF => {
+        let bank = if self.has_rumble {
+          self.rumble = (byte & 0b1000) != 0;
+          byte & 0b0111
+        } else {
+          byte & 0b1111
+        };
+        self.ram_index = usize::from(bank) % self.ram_banks.len();
+      }
+      0xA000..=0xBFFF if self.ram_active => {
+        let bank = &mut self.ram_banks[self.ram_index];
+        let index = usize::from(address) - 0xA000;
+        bank[index] = byte;
+      }
+      _ => (),
+    }
+  }
+  #[inline]
+  fn rumble(&self) -> bool {
+    self.rumble
+  }
+}
+
+#[test]
+fn test_mbc5_banking() {
+  use crate::system::System;
+  // mark each bank with its own index.
+  let mut rom = vec![0_u8; ROM_BANK_SIZE * 512];
+  for (i, bank) in rom.chunks_mut(ROM_BANK_SIZE).enumerate() {
+    bank[0x1000..0x1002].copy_from_slice(&(i as u16).to_le_bytes());
+  }
+  rom[0x147] = 0x1C;
+  rom[0x148] = 0x08;
+  let bank_in_4000 = |mbc5: &MBC5| u16::from_le_bytes([mbc5.read(0x5000), mbc5.read(0x5001)]);
+  let mut mbc5 = MBC5::new(&rom, None).unwrap();
+  assert_eq!(bank_in_4000(&mbc5), 1);
+  // the 9th bit is written separately.
+  mbc5.write(0x2000, 0x23);
+  mbc5.write(0x3000, 0x01);
+  assert_eq!(bank_in_4000(&mbc5), 0x123);
+  mbc5.write(0x3000, 0x00);
+  assert_eq!(bank_in_4000(&mbc5), 0x23);
+  // bank 0 can be picked too.
+  mbc5.write(0x2000, 0x00);
+  assert_eq!(bank_in_4000(&mbc5), 0);
+
+  // the program writes 0x08 to the ram bank register and then loops forever.
+  rom[0x100..0x107].copy_from_slice(&[0x3E, 0x08, 0xEA, 0x00, 0x40, 0x18, 0xFE]);
+  let mut system = System::from_cart(Box::new(MBC5::new(&rom, None).unwrap()));
+  assert!(!system.rumble());
+  system.run_cycles(100);
+  assert!(system.rumble());
+}
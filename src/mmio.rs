@@ -1,24 +1,56 @@
-use bitfrob::{u8_get_bit, u8_with_bit, u8_with_value};
+use bitfrob::{u16_get_bit, u8_get_bit, u8_with_bit, u8_with_value};
 
-use crate::{button_state::ButtonState, ppu::PpuMode, spare_parts::IrqTy};
+use crate::{
+  button_state::ButtonState,
+  ppu::PpuMode,
+  serial::{Disconnected, SerialDevice, SerialLogger},
+  spare_parts::IrqTy,
+};
 
-#[derive(Debug, Clone)]
+/// Which kind of Game Boy is being emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Model {
+  #[default]
+  Dmg,
+  Cgb,
+}
+
+#[derive(Debug)]
 pub struct MMIO {
   bytes: [u8; 256],
+  model: Model,
   button_state: ButtonState,
-  serial_log: Option<Vec<u8>>,
-  timer_sub_ticks: u16,
+  serial_device: Box<dyn SerialDevice>,
+  /// Bits shifted so far in the current serial transfer.
+  serial_bits: u8,
+  /// The internal counter that `DIV` shows the upper half of, and which also
+  /// drives the timer.
+  sys_counter: u16,
+  /// TIMA overflows get handled a little late, see [`TimaOverflow`].
+  tima_overflow: TimaOverflow,
+  /// All the enabled STAT interrupt sources OR-ed together. The interrupt only
+  /// fires when this goes from low to high.
+  stat_line: bool,
 }
 impl Default for MMIO {
   #[inline]
   fn default() -> Self {
     let mut out = Self {
       bytes: [0_u8; 256],
+      model: Model::Dmg,
       button_state: Default::default(),
-      serial_log: None,
-      timer_sub_ticks: TimerControl(0).sub_ticks(),
+      serial_device: Box::new(Disconnected),
+      serial_bits: 0,
+      sys_counter: 0,
+      tima_overflow: TimaOverflow::None,
+      stat_line: false,
     };
     out.write(MMIO::JOYP as _, 0_u8);
+    // the system starts just after the boot rom would have handed off control,
+    // so the LCD values are whatever the boot rom would have left behind.
+    out.bytes[MMIO::LCDC] = 0x91;
+    out.bytes[MMIO::BGP] = 0xFC;
+    out.set_sys_counter(0xABCC);
     out
   }
 }
@@ -26,67 +58,160 @@ impl MMIO {
   pub const JOYP: usize = 0x00;
   pub const SB: usize = 0x01;
   pub const SC: usize = 0x02;
+  pub const DIV: usize = 0x04;
   pub const TIMA: usize = 0x05;
   pub const TMA: usize = 0x06;
   pub const TAC: usize = 0x07;
   pub const IF: usize = 0x0F;
+  pub const LCDC: usize = 0x40;
   pub const STAT: usize = 0x41;
+  pub const SCY: usize = 0x42;
+  pub const SCX: usize = 0x43;
   pub const LY: usize = 0x44;
   pub const LYC: usize = 0x45;
+  pub const DMA: usize = 0x46;
+  pub const BGP: usize = 0x47;
+  pub const OBP0: usize = 0x48;
+  pub const OBP1: usize = 0x49;
+  pub const WY: usize = 0x4A;
+  pub const WX: usize = 0x4B;
+
+  #[inline]
+  pub fn model(&self) -> Model {
+    self.model
+  }
+  /// Changes which registers exist, and which of their bits can be read and
+  /// written. This should be picked before the system starts running.
+  #[inline]
+  pub fn set_model(&mut self, model: Model) {
+    self.model = model;
+  }
+  #[inline]
+  fn masks(&self) -> &'static [RegisterMask; 256] {
+    match self.model {
+      Model::Dmg => &DMG_MASKS,
+      Model::Cgb => &CGB_MASKS,
+    }
+  }
 
   #[inline]
   pub fn read(&self, index: u8) -> u8 {
-    self.bytes[usize::from(index)]
+    let byte = match usize::from(index) {
+      // the button lines are read live, so they're never stale.
+      MMIO::JOYP => {
+        let select = self.bytes[MMIO::JOYP];
+        let action = !u8_get_bit(5, select);
+        let direction = !u8_get_bit(4, select);
+        self.button_state.to_joyp(action, direction)
+      }
+      other => self.bytes[other],
+    };
+    byte | self.masks()[usize::from(index)].read_ones
   }
   #[inline]
   pub fn write(&mut self, index: u8, byte: u8) {
+    // bits that can't be written keep their old value.
+    let writable = self.masks()[usize::from(index)].writable;
+    let byte = (byte & writable) | (self.bytes[usize::from(index)] & !writable);
     match usize::from(index) {
       MMIO::JOYP => {
-        let action = !u8_get_bit(5, byte);
-        let direction = !u8_get_bit(4, byte);
-        self.bytes[MMIO::JOYP] = self.button_state.to_joyp(action, direction);
+        // selecting a row with a button held down pulls a line low too.
+        let old_joyp = self.joyp();
+        self.bytes[MMIO::JOYP] = byte;
+        self.check_joyp_edge(old_joyp);
       }
       MMIO::SC => {
         let sc = SerialControl(byte);
         if sc.transfer() {
-          let sb = self.sb();
-          if let Some(log) = &mut self.serial_log {
-            log.push(sb)
-          }
+          self.serial_bits = 0;
         }
         self.bytes[Self::SC] = byte;
       }
+      MMIO::DIV => {
+        // resetting the counter can make the timer's input bit fall.
+        let old_signal = self.timer_signal();
+        let old_counter = self.sys_counter;
+        self.set_sys_counter(0);
+        self.check_timer_edge(old_signal);
+        self.check_serial_edge(old_counter);
+      }
+      MMIO::TIMA => match self.tima_overflow {
+        // writing during the delay cancels the reload and the interrupt.
+        TimaOverflow::Delay => {
+          self.tima_overflow = TimaOverflow::None;
+          self.bytes[Self::TIMA] = byte;
+        }
+        // writing while TMA is being loaded doesn't do anything.
+        TimaOverflow::Reload => (),
+        TimaOverflow::None => self.bytes[Self::TIMA] = byte,
+      },
+      MMIO::TMA => {
+        self.bytes[Self::TMA] = byte;
+        // writing while TMA is being loaded also changes what TIMA gets.
+        if self.tima_overflow == TimaOverflow::Reload {
+          self.bytes[Self::TIMA] = byte;
+        }
+      }
+      MMIO::TAC => {
+        // changing the selected bit or turning off the timer can make the
+        // timer's input fall.
+        let old_signal = self.timer_signal();
+        self.bytes[Self::TAC] = byte;
+        self.check_timer_edge(old_signal);
+      }
       MMIO::STAT => {
         // bits 0..=2 are read-only to the CPU.
         let old_stat = self.bytes[Self::STAT];
         let fixed_stat = u8_with_value(0, 2, byte, old_stat);
         self.bytes[MMIO::STAT] = fixed_stat;
+        self.update_stat_line();
+      }
+      MMIO::LYC => {
+        self.bytes[MMIO::LYC] = byte;
+        self.update_lyc_match();
       }
       other => self.bytes[other] = byte,
     }
   }
+  /// Plugs a new device into the link port, giving back the old one.
+  #[inline]
+  pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+    core::mem::replace(&mut self.serial_device, device)
+  }
+  #[inline]
+  pub fn serial_device(&self) -> &dyn SerialDevice {
+    &*self.serial_device
+  }
+  #[inline]
+  pub fn serial_device_mut(&mut self) -> &mut dyn SerialDevice {
+    &mut *self.serial_device
+  }
+  /// Plugs in a [`SerialLogger`] (or unplugs everything).
   #[inline]
   pub fn set_serial_logging(&mut self, log: bool) {
     if log {
-      self.serial_log = Some(vec![]);
+      self.set_serial_device(Box::new(SerialLogger::default()));
     } else {
-      self.serial_log = None;
+      self.set_serial_device(Box::new(Disconnected));
     }
   }
   #[inline]
   pub fn serial_log(&self) -> &[u8] {
-    self.serial_log.as_deref().unwrap_or_default()
+    self.serial_device.log()
   }
 
   #[inline]
   pub fn set_button_state(&mut self, button_state: ButtonState) {
-    let old_joyp = self.joyp() & 0b1111;
+    let old_joyp = self.joyp();
     self.button_state = button_state;
-    // flag an interrupt if any of the low 4 bits were *not* clear and now they
-    // *are* clear.
-    let new_joyp = self.joyp() & 0b1111;
-    let diff_joyp = (old_joyp ^ new_joyp) & 0b1111;
-    if diff_joyp != 0 && new_joyp != 0 {
+    self.check_joyp_edge(old_joyp);
+  }
+  /// Flags the joypad interrupt if any of the low 4 bits of `JOYP` went from
+  /// high to low.
+  #[inline]
+  fn check_joyp_edge(&mut self, old_joyp: u8) {
+    let falling = old_joyp & !self.joyp() & 0b1111;
+    if falling != 0 {
       self.flag_interrupt(IrqTy::Joypad);
     }
   }
@@ -96,71 +221,203 @@ impl MMIO {
     if byte == 144 && self.bytes[Self::LY] < 144 {
       self.flag_interrupt(IrqTy::VBlank);
     }
-    // always keep the lyc match bit accurate.
-    self.bytes[Self::STAT] = u8_with_bit(2, self.bytes[Self::STAT], byte == self.lyc());
     self.bytes[Self::LY] = byte;
+    self.update_lyc_match();
   }
   #[inline]
   pub fn set_ppu_mode(&mut self, mode: PpuMode) {
     self.bytes[Self::STAT] = u8_with_value(0, 1, self.bytes[Self::STAT], mode as u8);
-    // TODO: trigger STAT interrupts... somehow?
+    self.update_stat_line();
+  }
+  /// Keeps the LYC match bit of STAT accurate.
+  #[inline]
+  fn update_lyc_match(&mut self) {
+    let lyc_match = self.ly() == self.lyc();
+    self.bytes[Self::STAT] = u8_with_bit(2, self.bytes[Self::STAT], lyc_match);
+    self.update_stat_line();
+  }
+  /// Flags the STAT interrupt if the STAT line just went high.
+  ///
+  /// Since all the sources share one line, a new source becoming active while
+  /// another is still active doesn't trigger another interrupt.
+  #[inline]
+  fn update_stat_line(&mut self) {
+    let stat = self.stat();
+    let line = match stat.mode() {
+      PpuMode::HBlank => stat.hblank_irq(),
+      PpuMode::VBlank => stat.vblank_irq(),
+      PpuMode::Scan => stat.oam_irq(),
+      PpuMode::Draw => false,
+    } || (stat.lyc_irq() && stat.lyc_match());
+    if line && !self.stat_line {
+      self.flag_interrupt(IrqTy::LCDSTAT);
+    }
+    self.stat_line = line;
   }
 
   #[inline]
-  pub fn m_cycle(&mut self) {
+  fn set_sys_counter(&mut self, sys_counter: u16) {
+    self.sys_counter = sys_counter;
+    self.bytes[Self::DIV] = (sys_counter >> 8) as u8;
+  }
+
+  /// The timer's input: the selected counter bit AND the timer enable bit.
+  #[inline]
+  fn timer_signal(&self) -> bool {
     let tac = self.tac();
-    // if timer enabled
-    if tac.enabled() {
-      debug_assert_ne!(self.timer_sub_ticks, 0);
-      self.timer_sub_ticks -= 1;
-      if self.timer_sub_ticks == 0 {
-        self.timer_sub_ticks = tac.sub_ticks();
-        let (new, overflow) = self.tima().overflowing_add(1);
-        if overflow {
-          self.bytes[Self::TIMA] = self.tma();
-          self.flag_interrupt(IrqTy::Timer);
-        } else {
-          self.bytes[Self::TIMA] = new;
-        }
+    tac.enabled() && u16_get_bit(tac.counter_bit(), self.sys_counter)
+  }
+  /// Ticks TIMA if the timer's input went from 1 to 0.
+  #[inline]
+  fn check_timer_edge(&mut self, old_signal: bool) {
+    if old_signal && !self.timer_signal() {
+      let (new, overflow) = self.tima().overflowing_add(1);
+      self.bytes[Self::TIMA] = new;
+      if overflow {
+        self.tima_overflow = TimaOverflow::Delay;
       }
     }
   }
 
+  #[inline]
+  pub fn m_cycle(&mut self) {
+    match self.tima_overflow {
+      TimaOverflow::Delay => {
+        self.bytes[Self::TIMA] = self.tma();
+        self.flag_interrupt(IrqTy::Timer);
+        self.tima_overflow = TimaOverflow::Reload;
+      }
+      TimaOverflow::Reload => self.tima_overflow = TimaOverflow::None,
+      TimaOverflow::None => (),
+    }
+    // the counter goes up once per T-cycle.
+    let old_signal = self.timer_signal();
+    let old_counter = self.sys_counter;
+    self.set_sys_counter(old_counter.wrapping_add(4));
+    self.check_timer_edge(old_signal);
+    self.serial_device.m_cycle(self.serial_control().transfer());
+    self.check_serial_edge(old_counter);
+    // transfers using an external clock go whenever the device says so.
+    let sc = self.serial_control();
+    if sc.transfer() && !sc.internal() {
+      if let Some(bit_in) = self.serial_device.external_clock(self.serial_bit_out()) {
+        self.shift_serial_bit(bit_in);
+      }
+    }
+  }
+
+  /// Shifts a serial bit if there's a transfer using the internal clock and
+  /// the clock's counter bit went from 1 to 0.
+  #[inline]
+  fn check_serial_edge(&mut self, old_counter: u16) {
+    let sc = self.serial_control();
+    if !(sc.transfer() && sc.internal()) {
+      return;
+    }
+    // the normal speed is 8192 Hz, and CGB fast mode is 262144 Hz.
+    let bit = if sc.fast() { 3 } else { 8 };
+    if u16_get_bit(bit, old_counter) && !u16_get_bit(bit, self.sys_counter) {
+      let bit_in = self.serial_device.exchange_bit(self.serial_bit_out());
+      self.shift_serial_bit(bit_in);
+    }
+  }
+  /// The next bit to be sent is the top bit of SB.
+  #[inline]
+  fn serial_bit_out(&self) -> bool {
+    u8_get_bit(7, self.sb())
+  }
+  /// Shifts one bit out of the top of SB and one bit into the bottom, ending
+  /// the transfer after 8 bits.
+  #[inline]
+  fn shift_serial_bit(&mut self, bit_in: bool) {
+    self.bytes[Self::SB] = (self.sb() << 1) | u8::from(bit_in);
+    self.serial_bits += 1;
+    if self.serial_bits == 8 {
+      self.serial_bits = 0;
+      self.bytes[Self::SC] = u8_with_bit(7, self.sc(), false);
+      self.flag_interrupt(IrqTy::Serial);
+    }
+  }
+
   #[inline]
   pub fn joyp(&self) -> u8 {
     self.read(Self::JOYP as u8)
   }
   #[inline]
   pub fn sb(&self) -> u8 {
-    self.read(Self::SB as u8)
+    self.bytes[Self::SB]
   }
   #[inline]
   pub fn sc(&self) -> u8 {
-    self.read(Self::SC as u8)
+    self.bytes[Self::SC]
+  }
+  #[inline]
+  pub fn serial_control(&self) -> SerialControl {
+    SerialControl(self.sc())
+  }
+  #[inline]
+  pub fn div(&self) -> u8 {
+    self.bytes[Self::DIV]
   }
   #[inline]
   pub fn tima(&self) -> u8 {
-    self.read(Self::TIMA as u8)
+    self.bytes[Self::TIMA]
   }
   #[inline]
   pub fn tma(&self) -> u8 {
-    self.read(Self::TMA as u8)
+    self.bytes[Self::TMA]
   }
   #[inline]
   pub fn tac(&self) -> TimerControl {
-    TimerControl(self.read(Self::TAC as u8))
+    TimerControl(self.bytes[Self::TAC])
   }
   #[inline]
   pub fn if_(&self) -> u8 {
-    self.read(Self::IF as u8)
+    self.bytes[Self::IF]
+  }
+  #[inline]
+  pub fn lcdc(&self) -> LcdControl {
+    LcdControl(self.bytes[Self::LCDC])
+  }
+  #[inline]
+  pub fn stat(&self) -> LcdStatus {
+    LcdStatus(self.bytes[Self::STAT])
+  }
+  #[inline]
+  pub fn scy(&self) -> u8 {
+    self.bytes[Self::SCY]
+  }
+  #[inline]
+  pub fn scx(&self) -> u8 {
+    self.bytes[Self::SCX]
   }
   #[inline]
   pub fn lyc(&self) -> u8 {
-    self.read(Self::LYC as u8)
+    self.bytes[Self::LYC]
   }
   #[inline]
   pub fn ly(&self) -> u8 {
-    self.read(Self::LY as u8)
+    self.bytes[Self::LY]
+  }
+  #[inline]
+  pub fn bgp(&self) -> u8 {
+    self.bytes[Self::BGP]
+  }
+  #[inline]
+  pub fn obp0(&self) -> u8 {
+    self.bytes[Self::OBP0]
+  }
+  #[inline]
+  pub fn obp1(&self) -> u8 {
+    self.bytes[Self::OBP1]
+  }
+  #[inline]
+  pub fn wy(&self) -> u8 {
+    self.bytes[Self::WY]
+  }
+  #[inline]
+  pub fn wx(&self) -> u8 {
+    self.bytes[Self::WX]
   }
 
   #[inline]
@@ -169,6 +426,82 @@ impl MMIO {
   }
 }
 
+/// How the CPU sees one IO register.
+#[derive(Debug, Clone, Copy)]
+struct RegisterMask {
+  /// Bits that always read as 1, because they're unused or write-only.
+  read_ones: u8,
+  /// Bits that CPU writes can change.
+  writable: u8,
+}
+
+static DMG_MASKS: [RegisterMask; 256] = register_masks(Model::Dmg);
+static CGB_MASKS: [RegisterMask; 256] = register_masks(Model::Cgb);
+
+const fn register_masks(model: Model) -> [RegisterMask; 256] {
+  let cgb = matches!(model, Model::Cgb);
+  let mut out = [RegisterMask { read_ones: 0xFF, writable: 0x00 }; 256];
+  let mut i = 0;
+  while i < 256 {
+    let (read_ones, writable) = match i {
+      // joypad and serial
+      0x00 => (0xC0, 0x30),
+      0x01 => (0x00, 0xFF),
+      0x02 if cgb => (0x7C, 0x83),
+      0x02 => (0x7E, 0x81),
+      // timer and interrupts
+      0x04..=0x06 => (0x00, 0xFF),
+      0x07 => (0xF8, 0x07),
+      0x0F => (0xE0, 0x1F),
+      // sound, where the length timers and frequencies are write-only.
+      0x10 => (0x80, 0x7F),
+      0x11 | 0x16 => (0x3F, 0xFF),
+      0x12 | 0x17 | 0x21 | 0x22 | 0x24 | 0x25 => (0x00, 0xFF),
+      0x13 | 0x18 | 0x1B | 0x1D | 0x20 => (0xFF, 0xFF),
+      0x14 | 0x19 | 0x1E => (0xBF, 0xC7),
+      0x1A => (0x7F, 0x80),
+      0x1C => (0x9F, 0x60),
+      0x23 => (0xBF, 0xC0),
+      0x26 => (0x70, 0x80),
+      0x30..=0x3F => (0x00, 0xFF),
+      // lcd, where LY can't be written and the low bits of STAT are read-only.
+      0x40 | 0x42 | 0x43 | 0x45..=0x4B => (0x00, 0xFF),
+      0x41 => (0x80, 0x78),
+      0x44 => (0x00, 0x00),
+      // cgb speed switch, banking, dma, infrared, and palettes
+      0x4D if cgb => (0x7E, 0x01),
+      0x4F if cgb => (0xFE, 0x01),
+      0x51..=0x54 if cgb => (0xFF, 0xFF),
+      0x55 if cgb => (0x00, 0xFF),
+      0x56 if cgb => (0x3C, 0xC1),
+      0x68 | 0x6A if cgb => (0x40, 0xBF),
+      0x69 | 0x6B if cgb => (0x00, 0xFF),
+      0x6C if cgb => (0xFE, 0x01),
+      0x70 if cgb => (0xF8, 0x07),
+      0x72..=0x74 if cgb => (0x00, 0xFF),
+      0x75 if cgb => (0x8F, 0x70),
+      0x76 | 0x77 if cgb => (0x00, 0x00),
+      // hram and IE
+      0x80..=0xFF => (0x00, 0xFF),
+      _ => (0xFF, 0x00),
+    };
+    out[i] = RegisterMask { read_ones, writable };
+    i += 1;
+  }
+  out
+}
+
+/// After TIMA overflows it reads as 0 for one M-cycle, and then TMA is loaded
+/// and the interrupt fires during the next M-cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimaOverflow {
+  None,
+  /// TIMA overflowed and is still 0.
+  Delay,
+  /// TMA was just loaded into TIMA.
+  Reload,
+}
+
 #[derive(Clone, Copy, Default)]
 #[repr(transparent)]
 pub struct SerialControl(u8);
@@ -204,14 +537,14 @@ impl TimerControl {
   pub fn enabled(self) -> bool {
     u8_get_bit(2, self.0)
   }
-  /// The number of M-cycle sub-ticks of the timer before the main value ticks.
+  /// The bit of the system counter that ticks the timer when it goes low.
   #[inline]
-  pub fn sub_ticks(self) -> u16 {
+  pub fn counter_bit(self) -> u32 {
     match self.0 & 0b11 {
-      0 => 256,
-      1 => 4,
-      2 => 16,
-      3 => 64,
+      0 => 9,
+      1 => 3,
+      2 => 5,
+      3 => 7,
       _ => unreachable!(),
     }
   }
@@ -220,7 +553,207 @@ impl core::fmt::Debug for TimerControl {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("TimerControl")
       .field("enabled", &self.enabled())
-      .field("sub_ticks", &self.sub_ticks())
+      .field("counter_bit", &self.counter_bit())
       .finish()
   }
 }
+
+#[derive(Clone, Copy, Default)]
+#[repr(transparent)]
+pub struct LcdControl(u8);
+impl LcdControl {
+  #[inline]
+  pub fn lcd_enabled(self) -> bool {
+    u8_get_bit(7, self.0)
+  }
+  /// Offset of the window tile map within VRAM.
+  #[inline]
+  pub fn win_tile_map(self) -> usize {
+    if u8_get_bit(6, self.0) {
+      0x1C00
+    } else {
+      0x1800
+    }
+  }
+  #[inline]
+  pub fn win_enabled(self) -> bool {
+    u8_get_bit(5, self.0)
+  }
+  /// If the BG and window use the unsigned tile data addressing starting at
+  /// `0x8000` (otherwise it's the signed addressing based at `0x9000`).
+  #[inline]
+  pub fn unsigned_tile_data(self) -> bool {
+    u8_get_bit(4, self.0)
+  }
+  /// Offset of the BG tile map within VRAM.
+  #[inline]
+  pub fn bg_tile_map(self) -> usize {
+    if u8_get_bit(3, self.0) {
+      0x1C00
+    } else {
+      0x1800
+    }
+  }
+  /// If objects are 8x16 (otherwise they're 8x8).
+  #[inline]
+  pub fn obj_tall(self) -> bool {
+    u8_get_bit(2, self.0)
+  }
+  #[inline]
+  pub fn obj_enabled(self) -> bool {
+    u8_get_bit(1, self.0)
+  }
+  /// On the DMG this bit turns off both the BG and the window.
+  #[inline]
+  pub fn bg_enabled(self) -> bool {
+    u8_get_bit(0, self.0)
+  }
+  /// Offset of the given tile's data within VRAM.
+  #[inline]
+  pub fn tile_data(self, tile_id: u8) -> usize {
+    if self.unsigned_tile_data() {
+      usize::from(tile_id) * 16
+    } else {
+      (0x1000 + i32::from(tile_id as i8) * 16) as usize
+    }
+  }
+}
+impl core::fmt::Debug for LcdControl {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LcdControl")
+      .field("lcd_enabled", &self.lcd_enabled())
+      .field("win_tile_map", &self.win_tile_map())
+      .field("win_enabled", &self.win_enabled())
+      .field("unsigned_tile_data", &self.unsigned_tile_data())
+      .field("bg_tile_map", &self.bg_tile_map())
+      .field("obj_tall", &self.obj_tall())
+      .field("obj_enabled", &self.obj_enabled())
+      .field("bg_enabled", &self.bg_enabled())
+      .finish()
+  }
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(transparent)]
+pub struct LcdStatus(u8);
+impl LcdStatus {
+  #[inline]
+  pub fn lyc_irq(self) -> bool {
+    u8_get_bit(6, self.0)
+  }
+  #[inline]
+  pub fn oam_irq(self) -> bool {
+    u8_get_bit(5, self.0)
+  }
+  #[inline]
+  pub fn vblank_irq(self) -> bool {
+    u8_get_bit(4, self.0)
+  }
+  #[inline]
+  pub fn hblank_irq(self) -> bool {
+    u8_get_bit(3, self.0)
+  }
+  #[inline]
+  pub fn lyc_match(self) -> bool {
+    u8_get_bit(2, self.0)
+  }
+  #[inline]
+  pub fn mode(self) -> PpuMode {
+    match self.0 & 0b11 {
+      0 => PpuMode::HBlank,
+      1 => PpuMode::VBlank,
+      2 => PpuMode::Scan,
+      3 => PpuMode::Draw,
+      _ => unreachable!(),
+    }
+  }
+}
+impl core::fmt::Debug for LcdStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("LcdStatus")
+      .field("lyc_irq", &self.lyc_irq())
+      .field("oam_irq", &self.oam_irq())
+      .field("vblank_irq", &self.vblank_irq())
+      .field("hblank_irq", &self.hblank_irq())
+      .field("lyc_match", &self.lyc_match())
+      .field("mode", &self.mode())
+      .finish()
+  }
+}
+
+#[test]
+fn test_tima_overflow() {
+  // TIMA falls every 4 M-cycles, starting from TIMA=0xFF.
+  fn timer_about_to_overflow() -> MMIO {
+    let mut mmio = MMIO::default();
+    mmio.write(MMIO::DIV as u8, 0);
+    mmio.write(MMIO::TAC as u8, 0b101);
+    mmio.write(MMIO::TMA as u8, 0x42);
+    mmio.write(MMIO::TIMA as u8, 0xFF);
+    mmio.write(MMIO::IF as u8, 0);
+    mmio
+  }
+  let timer_irq = |mmio: &MMIO| mmio.if_() & (1 << IrqTy::Timer as u8) != 0;
+
+  // TIMA is 0 for a cycle, then gets TMA and the interrupt.
+  let mut mmio = timer_about_to_overflow();
+  (0..4).for_each(|_| mmio.m_cycle());
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0);
+  assert!(!timer_irq(&mmio));
+  mmio.m_cycle();
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0x42);
+  assert!(timer_irq(&mmio));
+
+  // writing TIMA during the delay cancels the reload.
+  let mut mmio = timer_about_to_overflow();
+  (0..4).for_each(|_| mmio.m_cycle());
+  mmio.write(MMIO::TIMA as u8, 0x10);
+  mmio.m_cycle();
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0x10);
+  assert!(!timer_irq(&mmio));
+
+  // writing TIMA during the reload is ignored, but TMA goes through.
+  let mut mmio = timer_about_to_overflow();
+  (0..5).for_each(|_| mmio.m_cycle());
+  mmio.write(MMIO::TIMA as u8, 0x10);
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0x42);
+  mmio.write(MMIO::TMA as u8, 0x77);
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0x77);
+
+  // resetting DIV while the selected bit is high makes TIMA tick.
+  let mut mmio = timer_about_to_overflow();
+  mmio.write(MMIO::TIMA as u8, 0x20);
+  (0..2).for_each(|_| mmio.m_cycle());
+  mmio.write(MMIO::DIV as u8, 0);
+  assert_eq!(mmio.read(MMIO::TIMA as u8), 0x21);
+}
+
+#[test]
+fn test_register_masks() {
+  let mut mmio = MMIO::default();
+  mmio.write(MMIO::IF as u8, 0);
+  assert_eq!(mmio.read(MMIO::IF as u8), 0xE0);
+  mmio.write(MMIO::TAC as u8, 0);
+  assert_eq!(mmio.read(MMIO::TAC as u8), 0xF8);
+  mmio.write(MMIO::TAC as u8, 0xFF);
+  assert_eq!(mmio.read(MMIO::TAC as u8), 0xFF);
+  // the mode and LYC bits can't be written.
+  let stat = mmio.read(MMIO::STAT as u8);
+  mmio.write(MMIO::STAT as u8, 0);
+  assert_eq!(mmio.read(MMIO::STAT as u8), 0x80 | (stat & 0b111));
+  mmio.write(MMIO::STAT as u8, 0xFF);
+  assert_eq!(mmio.read(MMIO::STAT as u8), 0xF8 | (stat & 0b111));
+  // unmapped registers read all 1s no matter what.
+  mmio.write(0x03, 0);
+  assert_eq!(mmio.read(0x03), 0xFF);
+  // the SC clock speed bit only exists on CGB.
+  mmio.write(MMIO::SC as u8, 0b10);
+  assert_eq!(mmio.read(MMIO::SC as u8), 0x7E);
+
+  let mut mmio = MMIO::default();
+  mmio.set_model(Model::Cgb);
+  mmio.write(MMIO::SC as u8, 0);
+  assert_eq!(mmio.read(MMIO::SC as u8), 0x7C);
+  mmio.write(MMIO::SC as u8, 0b10);
+  assert_eq!(mmio.read(MMIO::SC as u8), 0x7E);
+}
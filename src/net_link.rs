@@ -0,0 +1,318 @@
+use std::{
+  collections::VecDeque,
+  io::{self, Read, Write},
+  net::{TcpListener, TcpStream, ToSocketAddrs},
+  time::Duration,
+};
+
+use crate::serial::SerialDevice;
+
+/// A link cable to another emulator process, over a TCP or Unix socket.
+///
+/// Both sides run in windows of some number of M-cycles. At the end of each
+/// window a side sends everything that happened on its end of the cable during
+/// that window, then waits for the other side's report of the same window.
+/// The other side's events then play back during the next window, exactly one
+/// window after they happened. This keeps the two emulators within one window
+/// of each other, and makes transfers come out the same no matter how the two
+/// processes get scheduled.
+///
+/// Since bits take a window to cross over, and the answering bit takes another
+/// window to come back, the window must be less than half the time between
+/// clock edges (128 M-cycles at normal speed) for transfers to work. CGB fast
+/// mode transfers are too quick for that. Similarly, the side using the
+/// external clock needs to be waiting at least a window before the first clock
+/// edge for its first bit to make it across in time.
+///
+/// Syncing every window is slow, so while neither side has had a transfer
+/// going for a whole window the windows stretch out to [`IDLE_WINDOW`]
+/// M-cycles. Both sides report if they were busy when they sync, so they
+/// always agree on how long the next window is. A transfer that starts during
+/// an idle window gets the same treatment as above: the side using the
+/// external clock should be waiting for at least one idle window before the
+/// first clock edge.
+///
+/// If the connection fails, or the other side takes longer than [`TIMEOUT`] to
+/// answer a sync, this acts like nothing is plugged in from then on.
+///
+/// [`IDLE_WINDOW`]: Self::IDLE_WINDOW
+/// [`TIMEOUT`]: Self::TIMEOUT
+#[derive(Debug)]
+pub struct NetLinkPort<S: Read + Write> {
+  stream: S,
+  /// The window length while either side is transferring.
+  busy_window: u16,
+  /// The length of the current window.
+  window: u16,
+  /// M-cycles so far within the current window.
+  window_cycle: u16,
+  /// Events from this side during the current window.
+  outgoing: Vec<LinkEvent>,
+  /// Events from the other side to play back during the current window.
+  incoming: VecDeque<LinkEvent>,
+  /// If this side had a transfer going at any point in the current window.
+  busy: bool,
+  /// The bit this side is putting on the wire.
+  bit_out: bool,
+  /// The bit the other side is putting on the wire.
+  peer_bit_out: bool,
+  /// A clock edge from the other side that arrived this M-cycle.
+  clock_edge: Option<bool>,
+  /// Set if the connection fails, after which this acts like nothing is
+  /// plugged in.
+  disconnected: bool,
+}
+impl<S: Read + Write> NetLinkPort<S> {
+  /// The default number of M-cycles per sync window.
+  pub const DEFAULT_WINDOW: u16 = 32;
+  /// The number of M-cycles per sync window while both sides are idle.
+  pub const IDLE_WINDOW: u16 = 2048;
+  /// How long to wait on the other side during a sync.
+  pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+  /// Uses an already connected stream, which should be the same kind of
+  /// stream with the same window size on both sides.
+  ///
+  /// Reads that time out count as a disconnect, so the stream should have a
+  /// read timeout set if the other side might hang.
+  pub fn new(stream: S, window: u16) -> Self {
+    assert!(window > 0);
+    Self {
+      stream,
+      busy_window: window,
+      window,
+      window_cycle: 0,
+      outgoing: Vec::new(),
+      incoming: VecDeque::new(),
+      busy: false,
+      bit_out: true,
+      peer_bit_out: true,
+      clock_edge: None,
+      disconnected: false,
+    }
+  }
+
+  #[inline]
+  pub fn is_connected(&self) -> bool {
+    !self.disconnected
+  }
+
+  /// Notes any change in the bit that this side is sending.
+  #[inline]
+  fn set_bit_out(&mut self, bit_out: bool) {
+    if bit_out != self.bit_out {
+      self.bit_out = bit_out;
+      self.outgoing.push(LinkEvent { cycle: self.window_cycle, edge: false, bit: bit_out });
+    }
+  }
+
+  /// Sends this window's events and then waits for the other side's events of
+  /// the same window.
+  ///
+  /// Each message is a busy flag byte, a `u32` event count, and then the
+  /// events. The next window is idle only if neither side was busy.
+  fn sync(&mut self) -> io::Result<()> {
+    let mut message = Vec::with_capacity(5 + self.outgoing.len() * 3);
+    message.push(u8::from(self.busy));
+    message.extend_from_slice(&(self.outgoing.len() as u32).to_le_bytes());
+    for event in self.outgoing.drain(..) {
+      message.extend_from_slice(&event.to_bytes());
+    }
+    self.stream.write_all(&message)?;
+    self.stream.flush()?;
+    let mut header = [0; 5];
+    self.stream.read_exact(&mut header)?;
+    let [peer_busy, count @ ..] = header;
+    for _ in 0..u32::from_le_bytes(count) {
+      let mut bytes = [0; 3];
+      self.stream.read_exact(&mut bytes)?;
+      self.incoming.push_back(LinkEvent::from_bytes(bytes));
+    }
+    let busy = self.busy || peer_busy != 0;
+    self.window = if busy { self.busy_window } else { Self::IDLE_WINDOW };
+    self.busy = false;
+    Ok(())
+  }
+}
+impl NetLinkPort<TcpStream> {
+  /// Waits for the other side to connect.
+  pub fn listen_tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+    let (stream, _) = TcpListener::bind(addr)?.accept()?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Self::TIMEOUT))?;
+    stream.set_write_timeout(Some(Self::TIMEOUT))?;
+    Ok(Self::new(stream, Self::DEFAULT_WINDOW))
+  }
+  /// Connects to the other side, which must already be listening.
+  pub fn connect_tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Self::TIMEOUT))?;
+    stream.set_write_timeout(Some(Self::TIMEOUT))?;
+    Ok(Self::new(stream, Self::DEFAULT_WINDOW))
+  }
+}
+#[cfg(unix)]
+impl NetLinkPort<std::os::unix::net::UnixStream> {
+  /// Waits for the other side to connect.
+  pub fn listen_unix(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+    let (stream, _) = std::os::unix::net::UnixListener::bind(path)?.accept()?;
+    stream.set_read_timeout(Some(Self::TIMEOUT))?;
+    stream.set_write_timeout(Some(Self::TIMEOUT))?;
+    Ok(Self::new(stream, Self::DEFAULT_WINDOW))
+  }
+  /// Connects to the other side, which must already be listening.
+  pub fn connect_unix(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Self::TIMEOUT))?;
+    stream.set_write_timeout(Some(Self::TIMEOUT))?;
+    Ok(Self::new(stream, Self::DEFAULT_WINDOW))
+  }
+}
+impl<S: Read + Write + core::fmt::Debug> SerialDevice for NetLinkPort<S> {
+  #[inline]
+  fn exchange_bit(&mut self, bit_out: bool) -> bool {
+    self.set_bit_out(bit_out);
+    if !self.disconnected {
+      self.outgoing.push(LinkEvent { cycle: self.window_cycle, edge: true, bit: bit_out });
+    }
+    self.peer_bit_out
+  }
+  #[inline]
+  fn external_clock(&mut self, bit_out: bool) -> Option<bool> {
+    self.set_bit_out(bit_out);
+    self.clock_edge.take()
+  }
+  fn m_cycle(&mut self, transfer: bool) {
+    if self.disconnected {
+      self.peer_bit_out = true;
+      return;
+    }
+    self.busy |= transfer;
+    // a played back edge is only there for the M-cycle it happened on.
+    self.clock_edge = None;
+    self.window_cycle += 1;
+    if self.window_cycle == self.window {
+      self.window_cycle = 0;
+      if self.sync().is_err() {
+        self.disconnected = true;
+        self.peer_bit_out = true;
+        return;
+      }
+    }
+    // events from a window that was longer than this one all play back at the
+    // end of this window.
+    let last_cycle = self.window_cycle == self.window - 1;
+    while let Some(event) =
+      self.incoming.front().filter(|e| last_cycle || e.cycle <= self.window_cycle)
+    {
+      if event.edge {
+        self.clock_edge = Some(event.bit);
+      } else {
+        self.peer_bit_out = event.bit;
+      }
+      self.incoming.pop_front();
+    }
+  }
+}
+
+/// Something that happened on one end of the cable.
+#[derive(Debug, Clone, Copy)]
+struct LinkEvent {
+  /// M-cycle within the window.
+  cycle: u16,
+  /// A clock edge carrying a bit (otherwise just a change in the bit sent).
+  edge: bool,
+  bit: bool,
+}
+impl LinkEvent {
+  #[inline]
+  fn to_bytes(self) -> [u8; 3] {
+    let [c0, c1] = self.cycle.to_le_bytes();
+    [c0, c1, (u8::from(self.edge) << 1) | u8::from(self.bit)]
+  }
+  #[inline]
+  fn from_bytes([c0, c1, flags]: [u8; 3]) -> Self {
+    Self { cycle: u16::from_le_bytes([c0, c1]), edge: flags & 0b10 != 0, bit: flags & 0b01 != 0 }
+  }
+}
+
+#[test]
+fn test_net_link_playback() {
+  /// Gives back canned messages from the other side, and keeps what's sent.
+  #[derive(Debug)]
+  struct Canned {
+    incoming: io::Cursor<Vec<u8>>,
+    sent: Vec<u8>,
+  }
+  impl Read for Canned {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      self.incoming.read(buf)
+    }
+  }
+  impl Write for Canned {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.sent.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  // window 0: busy, the bit goes low on cycle 1, then an edge on cycle 2.
+  // window 1: not busy, nothing happens.
+  let incoming = vec![1, 2, 0, 0, 0, 1, 0, 0, 2, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0];
+  let stream = Canned { incoming: io::Cursor::new(incoming), sent: Vec::new() };
+  let mut port = NetLinkPort::new(stream, 4);
+
+  // the first window is one short, since M-cycles count from 1.
+  let mut edges = Vec::new();
+  for cycle in 1..8 {
+    port.m_cycle(cycle < 4);
+    if cycle == 2 {
+      assert!(port.exchange_bit(false));
+    } else {
+      edges.push(port.external_clock(true));
+    }
+    if cycle == 4 {
+      // the other side's window 0 is only played back once it's over.
+      assert!(port.peer_bit_out);
+      assert_eq!(port.stream.sent, [1, 3, 0, 0, 0, 2, 0, 0, 2, 0, 2, 3, 0, 1]);
+    }
+    if cycle == 5 {
+      assert!(!port.peer_bit_out);
+    }
+  }
+  assert_eq!(edges, [None, None, None, None, Some(true), None]);
+
+  // neither side was busy in window 1, so window 2 is a long one.
+  port.m_cycle(false);
+  assert_eq!(port.window, NetLinkPort::<Canned>::IDLE_WINDOW);
+  let sent = port.stream.sent.len();
+  for _ in 1..NetLinkPort::<Canned>::IDLE_WINDOW {
+    port.m_cycle(false);
+  }
+  assert_eq!(port.stream.sent.len(), sent);
+  assert!(port.is_connected());
+
+  // the other side has nothing more to say, which is a disconnect.
+  port.m_cycle(false);
+  assert!(!port.is_connected());
+  assert!(port.exchange_bit(false));
+}
+
+#[test]
+fn test_net_link_timeout() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+  // the other end is connected, but never answers.
+  let (_peer, _) = listener.accept().unwrap();
+  stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+  let mut port = NetLinkPort::new(stream, 4);
+  for _ in 0..4 {
+    port.m_cycle(true);
+  }
+  assert!(!port.is_connected());
+  assert!(port.exchange_bit(false));
+  assert_eq!(port.external_clock(false), None);
+}
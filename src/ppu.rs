@@ -20,9 +20,15 @@ https://youtu.be/HyzD8pNlpwI?t=1756
 
 */
 
+use std::collections::VecDeque;
+
+use bitfrob::u8_get_bit;
 use pixel_formats::r8g8b8a8_Srgb;
 
-use crate::system::{LCD, LCD_HEIGHT, LCD_WIDTH};
+use crate::{
+  spare_parts::SpareParts,
+  system::{Shades, LCD, LCD_HEIGHT, LCD_WIDTH},
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct PPU {
@@ -30,6 +36,43 @@ pub struct PPU {
   next_x: u8,
   mode: PpuMode,
   scanline_ticks: u16,
+  /// The colors used for each shade when drawing to the LCD.
+  palette: DmgPalette,
+  /// If `LCDC` has the LCD turned off, which stops the PPU entirely.
+  lcd_off: bool,
+  /// The first line after the LCD turns on doesn't do an OAM scan, and reports
+  /// mode 0 instead of mode 2.
+  first_line: bool,
+  /// The first frame after the LCD turns on isn't displayed.
+  skip_frame: bool,
+  /// The window's own line counter, which only advances on lines where some of
+  /// the window was actually drawn.
+  win_line: u8,
+  /// Set once `LY == WY` has been seen during this frame.
+  win_y_triggered: bool,
+  /// If any window pixels were drawn on the current line.
+  win_drawn: bool,
+  /// With `WX == 166` the window starting on the last pixel of a line carries
+  /// over and covers the entire next line.
+  win_full_line: bool,
+  win_full_next_line: bool,
+  /// The objects selected during the OAM scan of the current line, ordered by
+  /// their drawing priority once the scan is over.
+  line_objs: [OamEntry; 10],
+  line_obj_count: usize,
+  /// Index of the next object in `line_objs` to be fetched during drawing.
+  next_obj: usize,
+  /// Dots spent so far on the current object fetch.
+  obj_fetch_ticks: u8,
+  fetcher: Fetcher,
+  /// BG/window color indexes waiting to be drawn.
+  bg_fifo: VecDeque<u8>,
+  /// Object pixels waiting to be mixed with the BG/window pixels.
+  obj_fifo: VecDeque<ObjPixel>,
+  /// BG/window pixels to throw away before drawing more of the line.
+  discard: u8,
+  /// Dots to wait before the fetcher starts at the beginning of drawing.
+  draw_delay: u8,
 }
 impl PPU {
   /// time spend scanning OAM
@@ -38,22 +81,63 @@ impl PPU {
   const DOTS_HORIZONTAL: u16 = 456;
   /// current line is always in `0..=LINES_VERTICAL`
   const LINES_VERTICAL: u8 = 153;
+  /// The most objects that the OAM scan can select for a single line.
+  const OBJS_PER_LINE: usize = 10;
+  /// The first tile fetched each line is thrown away, so drawing always starts
+  /// one whole fetch late.
+  const FIRST_FETCH_TIME: u8 = 6;
+  /// Object fetches take this long once the BG fetcher is out of the way.
+  const OBJ_FETCH_TIME: u8 = 6;
 
-  pub fn t_cycle(&mut self, lcd: &mut LCD) -> PpuMode {
+  pub fn t_cycle(&mut self, lcd: &mut LCD, shades: &mut Shades, parts: &SpareParts) -> PpuMode {
+    let lcd_enabled = parts.mmio().lcdc().lcd_enabled();
+    if self.lcd_off {
+      if !lcd_enabled {
+        return self.mode();
+      }
+      // the LCD turns back on at the start of line 0.
+      self.lcd_off = false;
+      self.first_line = true;
+      self.skip_frame = true;
+    } else if !lcd_enabled {
+      self.lcd_off = true;
+      self.next_y = 0;
+      self.next_x = 0;
+      self.scanline_ticks = 0;
+      self.win_line = 0;
+      self.win_y_triggered = false;
+      self.win_drawn = false;
+      self.win_full_next_line = false;
+      self.mode = PpuMode::Scan;
+      shades.fill(0);
+      lcd.fill(self.palette.0[0]);
+      return self.mode();
+    }
     match self.mode {
       PpuMode::Scan => {
+        if self.scanline_ticks == 0 {
+          self.win_y_triggered |= parts.mmio().wy() == self.next_y;
+          self.win_full_line = self.win_full_next_line;
+          self.win_full_next_line = false;
+          self.line_obj_count = 0;
+        }
+        // each OAM entry takes 2 dots to check.
+        if self.scanline_ticks & 1 == 0 && !self.first_line {
+          self.scan_oam_entry(parts, usize::from(self.scanline_ticks / 2));
+        }
         self.scanline_ticks += 1;
         if self.scanline_ticks == Self::SCAN_TIME {
+          // On the DMG, the object with the smaller X is drawn on top, and
+          // then the earlier OAM entry wins ties (which a stable sort keeps).
+          self.line_objs[..self.line_obj_count].sort_by_key(|obj| obj.x);
+          self.start_drawing(parts);
+          self.first_line = false;
           self.mode = PpuMode::Draw;
         }
       }
       PpuMode::Draw => {
         self.scanline_ticks += 1;
-        let i = ((self.next_y as usize) * (LCD_WIDTH as usize)) + (self.next_x as usize);
-        lcd[i].r ^= 0xFF;
-        lcd[i].g ^= 0xF0;
-        lcd[i].b ^= 0x0F;
-        self.next_x += 1;
+        self.draw_dot(lcd, shades, parts);
         if self.next_x >= LCD_WIDTH {
           self.mode = PpuMode::HBlank;
         }
@@ -64,6 +148,10 @@ impl PPU {
           self.next_x = 0;
           self.scanline_ticks = 0;
           self.next_y += 1;
+          if self.win_drawn {
+            self.win_line += 1;
+            self.win_drawn = false;
+          }
           if self.next_y >= LCD_HEIGHT {
             self.mode = PpuMode::VBlank;
           } else {
@@ -76,21 +164,344 @@ impl PPU {
         if self.scanline_ticks == Self::DOTS_HORIZONTAL {
           self.next_y += 1;
           self.scanline_ticks = 0;
-          if self.next_y >= Self::LINES_VERTICAL {
+          if self.next_y > Self::LINES_VERTICAL {
             self.next_y = 0;
+            self.win_line = 0;
+            self.win_y_triggered = false;
+            self.win_full_next_line = false;
+            self.skip_frame = false;
             self.mode = PpuMode::Scan;
           }
         }
       }
     }
-    self.mode
+    self.mode()
+  }
+
+  /// Selects the OAM entry for the current line if it's in range.
+  fn scan_oam_entry(&mut self, parts: &SpareParts, index: usize) {
+    if self.line_obj_count >= Self::OBJS_PER_LINE {
+      return;
+    }
+    let obj = parts.oam()[index];
+    let height = if parts.mmio().lcdc().obj_tall() { 16 } else { 8 };
+    // object Y values are the screen position plus 16.
+    let line = u16::from(self.next_y) + 16;
+    let top = u16::from(obj.y);
+    if top <= line && line < top + height {
+      self.line_objs[self.line_obj_count] = obj;
+      self.line_obj_count += 1;
+    }
+  }
+
+  fn start_drawing(&mut self, parts: &SpareParts) {
+    self.fetcher = Fetcher::default();
+    self.bg_fifo.clear();
+    self.obj_fifo.clear();
+    self.next_obj = 0;
+    self.obj_fetch_ticks = 0;
+    // the fine scroll is handled by throwing away part of the first tile.
+    self.discard = parts.mmio().scx() & 7;
+    self.draw_delay = Self::FIRST_FETCH_TIME;
+  }
+
+  /// Runs the fetchers and FIFOs for one dot of drawing, possibly pushing a
+  /// pixel to the LCD.
+  fn draw_dot(&mut self, lcd: &mut LCD, shades: &mut Shades, parts: &SpareParts) {
+    if self.draw_delay > 0 {
+      self.draw_delay -= 1;
+      return;
+    }
+    self.check_win_start(parts);
+    if self.obj_fetch_pending(parts) {
+      // the object fetch has to wait for the BG fetcher to get past its tile
+      // and low data steps, and pixels don't move while either of those goes
+      // on.
+      let fetcher_busy = matches!(self.fetcher.step, FetchStep::Tile | FetchStep::DataLow);
+      if fetcher_busy || self.bg_fifo.is_empty() {
+        self.fetcher_tick(parts);
+      } else {
+        self.obj_fetch_ticks += 1;
+        if self.obj_fetch_ticks == Self::OBJ_FETCH_TIME {
+          self.obj_fetch_ticks = 0;
+          self.fetch_obj(parts);
+        }
+      }
+      return;
+    }
+    self.fetcher_tick(parts);
+    let Some(bg_color) = self.bg_fifo.pop_front() else {
+      return;
+    };
+    if self.discard > 0 {
+      self.discard -= 1;
+      return;
+    }
+    let obj_pixel = self.obj_fifo.pop_front();
+    let shade = self.mix_pixel(parts, bg_color, obj_pixel);
+    if !self.skip_frame {
+      let i = ((self.next_y as usize) * (LCD_WIDTH as usize)) + (self.next_x as usize);
+      shades[i] = shade;
+      lcd[i] = self.palette.0[usize::from(shade)];
+    }
+    self.next_x += 1;
+  }
+
+  /// Moves the BG fetcher forward by one dot.
+  fn fetcher_tick(&mut self, parts: &SpareParts) {
+    let mmio = parts.mmio();
+    let lcdc = mmio.lcdc();
+    let vram = &parts.vram()[0];
+    let fetcher = &mut self.fetcher;
+    let y = if fetcher.window { self.win_line } else { self.next_y.wrapping_add(mmio.scy()) };
+    if fetcher.step == FetchStep::Push {
+      // pushing only works once the FIFO is empty, and is retried every dot.
+      if self.bg_fifo.is_empty() {
+        self.bg_fifo.extend((0..8).map(|x| row_color(fetcher.low, fetcher.high, x)));
+        fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+        fetcher.step = FetchStep::Tile;
+      }
+      return;
+    }
+    // the other steps each take 2 dots.
+    fetcher.step_ticks += 1;
+    if fetcher.step_ticks < 2 {
+      return;
+    }
+    fetcher.step_ticks = 0;
+    match fetcher.step {
+      FetchStep::Tile => {
+        let (tile_map, x) = if fetcher.window {
+          (lcdc.win_tile_map(), fetcher.tile_x)
+        } else {
+          // the BG map is 32x32 tiles and wraps around at the edges.
+          (lcdc.bg_tile_map(), (mmio.scx() / 8).wrapping_add(fetcher.tile_x) & 31)
+        };
+        let map_index = tile_map + usize::from(y / 8) * 32 + usize::from(x & 31);
+        fetcher.tile_data = lcdc.tile_data(vram[map_index]);
+        fetcher.step = FetchStep::DataLow;
+      }
+      FetchStep::DataLow => {
+        fetcher.low = vram[fetcher.tile_data + usize::from(y % 8) * 2];
+        fetcher.step = FetchStep::DataHigh;
+      }
+      FetchStep::DataHigh => {
+        fetcher.high = vram[fetcher.tile_data + usize::from(y % 8) * 2 + 1];
+        fetcher.step = FetchStep::Push;
+      }
+      FetchStep::Push => unreachable!(),
+    }
+  }
+
+  /// Switches the fetcher over to the window if the window starts at the
+  /// current screen position.
+  fn check_win_start(&mut self, parts: &SpareParts) {
+    let mmio = parts.mmio();
+    if self.fetcher.window || !mmio.lcdc().win_enabled() {
+      return;
+    }
+    let discard = if self.win_full_line {
+      if self.next_x != 0 {
+        return;
+      }
+      0
+    } else {
+      if !self.win_y_triggered {
+        return;
+      }
+      let wx = match mmio.wx() {
+        // the window "stutters" based on the fine scroll of the BG.
+        0 => mmio.scx() & 7,
+        wx => wx,
+      };
+      if wx < 7 {
+        // the window starts off the left edge, so part of it is thrown away.
+        if self.next_x != 0 {
+          return;
+        }
+        7 - wx
+      } else {
+        if self.next_x + 7 != wx {
+          return;
+        }
+        // the window starts on the very last pixel and then covers the next
+        // line too.
+        if wx == 166 {
+          self.win_full_next_line = true;
+        }
+        0
+      }
+    };
+    self.win_drawn = true;
+    self.discard = discard;
+    self.bg_fifo.clear();
+    self.fetcher = Fetcher { window: true, ..Fetcher::default() };
+  }
+
+  /// If the next object of the line should be fetched before drawing the
+  /// current screen position.
+  fn obj_fetch_pending(&self, parts: &SpareParts) -> bool {
+    // object X values are the screen position plus 8.
+    parts.mmio().lcdc().obj_enabled()
+      && self.next_obj < self.line_obj_count
+      && self.line_objs[self.next_obj].x <= self.next_x + 8
+  }
+
+  /// Fetches the next object of the line and mixes it into the object FIFO.
+  fn fetch_obj(&mut self, parts: &SpareParts) {
+    let obj = self.line_objs[self.next_obj];
+    self.next_obj += 1;
+    let lcdc = parts.mmio().lcdc();
+    let vram = &parts.vram()[0];
+    let height = if lcdc.obj_tall() { 16 } else { 8 };
+    let row = self.next_y + 16 - obj.y;
+    // the object size can change after the OAM scan, possibly leaving a
+    // selected object out of range.
+    if row >= height {
+      return;
+    }
+    let row = if obj.y_flip() { height - 1 - row } else { row };
+    // tall objects ignore the low bit and always use an even/odd tile pair.
+    let tile_id = if lcdc.obj_tall() { obj.tile & !1 } else { obj.tile };
+    // objects always use the unsigned tile data addressing.
+    let row_data = usize::from(tile_id) * 16 + usize::from(row) * 2;
+    let (low, high) = (vram[row_data], vram[row_data + 1]);
+    // columns left of the current screen position have already gone by.
+    let skip = self.next_x + 8 - obj.x;
+    for (i, col) in (skip..8).enumerate() {
+      let col = if obj.x_flip() { 7 - col } else { col };
+      let pixel = ObjPixel { color: row_color(low, high, col), obj };
+      // earlier objects keep their pixels unless they were transparent.
+      match self.obj_fifo.get_mut(i) {
+        Some(old) if old.color == 0 => *old = pixel,
+        Some(_) => (),
+        None => self.obj_fifo.push_back(pixel),
+      }
+    }
+  }
+
+  /// Determines the final shade from the BG/window and object pixels.
+  fn mix_pixel(&self, parts: &SpareParts, bg_color: u8, obj_pixel: Option<ObjPixel>) -> u8 {
+    let mmio = parts.mmio();
+    let lcdc = mmio.lcdc();
+    let bg_color = if lcdc.bg_enabled() { bg_color } else { 0 };
+    match obj_pixel {
+      Some(ObjPixel { color, obj })
+        if color != 0 && lcdc.obj_enabled() && !(obj.bg_priority() && bg_color != 0) =>
+      {
+        let palette = if obj.obp1() { mmio.obp1() } else { mmio.obp0() };
+        palette_shade(palette, color)
+      }
+      _ => palette_shade(mmio.bgp(), bg_color),
+    }
   }
 
   pub fn current_scanline(&self) -> u8 {
     self.next_y
   }
+  #[inline]
+  pub fn palette(&self) -> DmgPalette {
+    self.palette
+  }
+  #[inline]
+  pub fn set_palette(&mut self, palette: DmgPalette) {
+    self.palette = palette;
+  }
+
+  /// The mode as seen through `STAT`.
   pub fn mode(&self) -> PpuMode {
-    self.mode
+    if self.lcd_off || (self.first_line && self.mode == PpuMode::Scan) {
+      PpuMode::HBlank
+    } else {
+      self.mode
+    }
+  }
+  pub fn lcd_enabled(&self) -> bool {
+    !self.lcd_off
+  }
+  /// The 8-byte row of OAM being read while the PPU scans OAM, if it is.
+  pub fn oam_scan_row(&self) -> Option<usize> {
+    if self.lcd_off || self.first_line || self.mode != PpuMode::Scan {
+      None
+    } else {
+      Some(usize::from(self.scanline_ticks / 4))
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum FetchStep {
+  #[default]
+  Tile,
+  DataLow,
+  DataHigh,
+  Push,
+}
+
+/// Fetches rows of 8 BG or window pixels for the BG FIFO.
+#[derive(Debug, Clone, Default)]
+struct Fetcher {
+  step: FetchStep,
+  step_ticks: u8,
+  /// If this is fetching the window (otherwise the BG).
+  window: bool,
+  /// Tiles fetched so far this line.
+  tile_x: u8,
+  /// Offset of the current tile's data within VRAM.
+  tile_data: usize,
+  low: u8,
+  high: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ObjPixel {
+  color: u8,
+  obj: OamEntry,
+}
+
+/// Gets the 2-bit color index of a pixel within one row of tile data.
+///
+/// Each row of a tile is two bytes, the low bits of every pixel and then the
+/// high bits of every pixel, with the leftmost pixel in bit 7.
+#[inline]
+pub(crate) fn row_color(low: u8, high: u8, x: u8) -> u8 {
+  let bit = 7 - u32::from(x);
+  let low = u8_get_bit(bit, low) as u8;
+  let high = u8_get_bit(bit, high) as u8;
+  (high << 1) | low
+}
+
+/// Looks up the shade of a color index within a `BGP`/`OBP0`/`OBP1` value.
+#[inline]
+pub(crate) fn palette_shade(palette: u8, color: u8) -> u8 {
+  (palette >> (color * 2)) & 0b11
+}
+
+/// The colors used for each of the 4 shades, from lightest to darkest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmgPalette(pub [r8g8b8a8_Srgb; 4]);
+impl Default for DmgPalette {
+  #[inline]
+  fn default() -> Self {
+    Self::GRAYSCALE
+  }
+}
+impl DmgPalette {
+  /// Evenly spaced neutral grays.
+  pub const GRAYSCALE: Self = Self::from_rgb([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+  /// The yellow-green of the original Game Boy screen.
+  pub const DMG: Self = Self::from_rgb([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F]);
+  /// The slightly tinted grays of the Game Boy Pocket screen.
+  pub const POCKET: Self = Self::from_rgb([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F]);
+  /// The blue-green backlight of the Game Boy Light screen.
+  pub const LIGHT: Self = Self::from_rgb([0x00B581, 0x009A71, 0x00694A, 0x004F3B]);
+
+  /// Makes a palette from `0xRRGGBB` values.
+  pub const fn from_rgb(rgb: [u32; 4]) -> Self {
+    const fn color(rgb: u32) -> r8g8b8a8_Srgb {
+      r8g8b8a8_Srgb { r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: rgb as u8, a: 0xFF }
+    }
+    Self([color(rgb[0]), color(rgb[1]), color(rgb[2]), color(rgb[3])])
   }
 }
 
@@ -102,8 +513,28 @@ pub struct OamEntry {
   pub tile: u8,
   pub flags: u8,
 }
+impl OamEntry {
+  /// If the BG and window are drawn over this object (unless they're color 0).
+  #[inline]
+  pub fn bg_priority(self) -> bool {
+    u8_get_bit(7, self.flags)
+  }
+  #[inline]
+  pub fn y_flip(self) -> bool {
+    u8_get_bit(6, self.flags)
+  }
+  #[inline]
+  pub fn x_flip(self) -> bool {
+    u8_get_bit(5, self.flags)
+  }
+  /// If the object uses `OBP1` (otherwise `OBP0`).
+  #[inline]
+  pub fn obp1(self) -> bool {
+    u8_get_bit(4, self.flags)
+  }
+}
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[repr(u8)]
 pub enum PpuMode {
   HBlank = 0,
@@ -112,3 +543,157 @@ pub enum PpuMode {
   Scan = 2,
   Draw = 3,
 }
+
+/// A PPU and parts with a blank cart, BGP/OBP0 as the identity palette, and
+/// tile 1 as solid color 3.
+#[cfg(test)]
+fn test_ppu() -> (PPU, Box<(LCD, Shades)>, SpareParts) {
+  use crate::cpu::CpuView;
+  let mut parts = SpareParts::from_cart(Box::new(vec![0_u8; 0x8000]));
+  parts.write(0xFF40, 0b1001_0011);
+  parts.write(0xFF42, 0);
+  parts.write(0xFF43, 0);
+  parts.write(0xFF47, 0b1110_0100);
+  parts.write(0xFF48, 0b1110_0100);
+  for address in 0x8010..0x8020 {
+    parts.write(address, 0xFF);
+  }
+  let lcd = Box::new((
+    [r8g8b8a8_Srgb::OPAQUE_BLACK; crate::system::LCD_PIXEL_COUNT],
+    [0; crate::system::LCD_PIXEL_COUNT],
+  ));
+  (PPU::default(), lcd, parts)
+}
+
+/// Runs the PPU until it reaches the start of line `y`.
+#[cfg(test)]
+fn run_to_line(ppu: &mut PPU, lcd: &mut (LCD, Shades), parts: &SpareParts, y: u8) {
+  loop {
+    ppu.t_cycle(&mut lcd.0, &mut lcd.1, parts);
+    if ppu.current_scanline() == y && ppu.scanline_ticks == 0 {
+      return;
+    }
+  }
+}
+
+/// Runs all of line `y`, giving how many dots were spent drawing.
+#[cfg(test)]
+fn draw_time(ppu: &mut PPU, lcd: &mut (LCD, Shades), parts: &SpareParts, y: u8) -> u16 {
+  run_to_line(ppu, lcd, parts, y);
+  let mut dots = 0;
+  while ppu.current_scanline() == y {
+    ppu.t_cycle(&mut lcd.0, &mut lcd.1, parts);
+    if matches!(ppu.mode(), PpuMode::Draw) {
+      dots += 1;
+    }
+  }
+  dots
+}
+
+#[cfg(test)]
+fn shade_at(lcd: &(LCD, Shades), x: usize, y: usize) -> u8 {
+  lcd.1[y * usize::from(LCD_WIDTH) + x]
+}
+
+#[test]
+fn test_ppu_draw_time() {
+  use crate::cpu::CpuView;
+  let (mut ppu, mut lcd, mut parts) = test_ppu();
+  assert_eq!(draw_time(&mut ppu, &mut lcd, &parts, 5), 172);
+
+  // the fine scroll is thrown away one dot per pixel.
+  parts.write(0xFF43, 3);
+  assert_eq!(draw_time(&mut ppu, &mut lcd, &parts, 6), 175);
+  parts.write(0xFF43, 0);
+
+  // an object aligned with the BG tiles costs the full 11 dots.
+  parts.write(0xFE00, 16 + 7);
+  parts.write(0xFE01, 16);
+  assert_eq!(draw_time(&mut ppu, &mut lcd, &parts, 7), 183);
+}
+
+#[test]
+fn test_ppu_bg_scroll_wrap() {
+  use crate::cpu::CpuView;
+  let (mut ppu, mut lcd, mut parts) = test_ppu();
+  // bottom right corner of the BG map.
+  parts.write(0x9800 + 31 * 32 + 31, 1);
+
+  parts.write(0xFF42, 252);
+  parts.write(0xFF43, 252);
+  run_to_line(&mut ppu, &mut lcd, &parts, 5);
+  for (x, y) in [(0, 0), (3, 0), (0, 3), (3, 3)] {
+    assert_eq!(shade_at(&lcd, x, y), 3);
+  }
+  for (x, y) in [(4, 0), (0, 4), (4, 4), (159, 3)] {
+    assert_eq!(shade_at(&lcd, x, y), 0);
+  }
+}
+
+#[test]
+fn test_ppu_window_line_counter() {
+  use crate::cpu::CpuView;
+  let (mut ppu, mut lcd, mut parts) = test_ppu();
+  // tile 2 is solid color 1 and fills the BG.
+  for address in (0x8020..0x8030).step_by(2) {
+    parts.write(address, 0xFF);
+  }
+  for address in 0x9800..0x9C00 {
+    parts.write(address, 2);
+  }
+  // window rows are tile 1, then tile 0, then tile 1 again.
+  for address in (0x9C00..0x9C20).chain(0x9C40..0x9C60) {
+    parts.write(address, 1);
+  }
+  parts.write(0xFF4A, 0);
+  parts.write(0xFF4B, 7);
+  parts.write(0xFF40, 0b1111_0001);
+
+  run_to_line(&mut ppu, &mut lcd, &parts, 8);
+  parts.write(0xFF40, 0b1101_0001);
+  run_to_line(&mut ppu, &mut lcd, &parts, 16);
+  parts.write(0xFF40, 0b1111_0001);
+  run_to_line(&mut ppu, &mut lcd, &parts, 17);
+
+  assert_eq!(shade_at(&lcd, 0, 7), 3);
+  assert_eq!(shade_at(&lcd, 0, 8), 1);
+  // the window picks up with its 9th line, not the 17th.
+  assert_eq!(shade_at(&lcd, 0, 16), 0);
+  assert_eq!(shade_at(&lcd, 159, 16), 0);
+}
+
+#[test]
+fn test_ppu_object_selection() {
+  use crate::cpu::CpuView;
+  let (mut ppu, mut lcd, mut parts) = test_ppu();
+  // 11 objects in a row on lines 0 to 7, only 10 of which get drawn.
+  for i in 0..11 {
+    parts.write(0xFE00 + i * 4, 16);
+    parts.write(0xFE01 + i * 4, 8 + 8 * i as u8);
+    parts.write(0xFE02 + i * 4, 1);
+  }
+  // on lines 16 to 23 an object with OBP1, and a later one further left.
+  parts.write(0xFF49, 0b0101_0100);
+  parts.write(0xFE00 + 11 * 4, 32);
+  parts.write(0xFE01 + 11 * 4, 20);
+  parts.write(0xFE02 + 11 * 4, 1);
+  parts.write(0xFE03 + 11 * 4, 0b0001_0000);
+  parts.write(0xFE00 + 12 * 4, 32);
+  parts.write(0xFE01 + 12 * 4, 18);
+  parts.write(0xFE02 + 12 * 4, 1);
+  run_to_line(&mut ppu, &mut lcd, &parts, 24);
+
+  for x in 0..80 {
+    assert_eq!(shade_at(&lcd, x, 0), 3);
+  }
+  assert_eq!(shade_at(&lcd, 80, 0), 0);
+  assert_eq!(shade_at(&lcd, 87, 7), 0);
+
+  // the smaller X is on top even though it's later in OAM.
+  assert_eq!(shade_at(&lcd, 9, 16), 0);
+  assert_eq!(shade_at(&lcd, 10, 16), 3);
+  assert_eq!(shade_at(&lcd, 17, 16), 3);
+  assert_eq!(shade_at(&lcd, 18, 16), 1);
+  assert_eq!(shade_at(&lcd, 19, 16), 1);
+  assert_eq!(shade_at(&lcd, 20, 16), 0);
+}
@@ -0,0 +1,283 @@
+use std::io::{self, Write};
+
+use crate::{
+  ppu::{palette_shade, row_color},
+  serial::SerialDevice,
+};
+
+/// A Game Boy Printer plugged into the link port.
+///
+/// The Game Boy sends the printer packets of bytes, and the printer answers
+/// each byte with a byte of its own:
+///
+/// | Bytes | Sent | Answer |
+/// |:-:|:-|:-|
+/// | 2 | magic bytes `0x88, 0x33` | `0x00` |
+/// | 1 | command | `0x00` |
+/// | 1 | compression flag | `0x00` |
+/// | 2 | data length (little endian) | `0x00` |
+/// | N | data | `0x00` |
+/// | 2 | checksum of command through data (little endian) | `0x00` |
+/// | 1 | `0x00` | `0x81` (the printer is there) |
+/// | 1 | `0x00` | status byte |
+///
+/// Each row of tiles that gets printed is added to the bottom of the printed
+/// image, which is always [`PRINT_WIDTH`](Self::PRINT_WIDTH) pixels wide.
+#[derive(Debug, Clone, Default)]
+pub struct GbPrinter {
+  /// The byte being shifted in right now.
+  byte_in: u8,
+  /// The byte being shifted out right now.
+  byte_out: u8,
+  bits: u8,
+  /// The bytes of the current packet so far.
+  packet: Vec<u8>,
+  /// Tile data waiting to be printed.
+  tile_data: Vec<u8>,
+  status: u8,
+  /// M-cycles left until the current print job is done.
+  busy_cycles: u32,
+  /// The 2-bit shade of each printed pixel.
+  image: Vec<u8>,
+}
+impl GbPrinter {
+  /// The printer's paper fits 20 tiles across.
+  pub const PRINT_WIDTH: usize = 160;
+
+  const CMD_INIT: u8 = 0x01;
+  const CMD_PRINT: u8 = 0x02;
+  const CMD_DATA: u8 = 0x04;
+  const CMD_STATUS: u8 = 0x0F;
+
+  const STATUS_CHECKSUM_ERROR: u8 = 1 << 0;
+  const STATUS_PRINTING: u8 = 1 << 1;
+  const STATUS_DATA_FULL: u8 = 1 << 2;
+  const STATUS_UNPROCESSED_DATA: u8 = 1 << 3;
+
+  /// Each data packet usually holds 2 rows of tiles.
+  const BAND_SIZE: usize = 20 * 2 * 16;
+  /// The printer's memory holds 9 bands.
+  const BUFFER_SIZE: usize = Self::BAND_SIZE * 9;
+  /// Printing takes about half a second.
+  const PRINT_TIME: u32 = 1024 * 1024 / 2;
+
+  /// The 2-bit shade of each printed pixel, [`PRINT_WIDTH`](Self::PRINT_WIDTH)
+  /// pixels per row.
+  #[inline]
+  pub fn image(&self) -> &[u8] {
+    &self.image
+  }
+  #[inline]
+  pub fn image_height(&self) -> usize {
+    self.image.len() / Self::PRINT_WIDTH
+  }
+  /// Throws away everything printed so far.
+  #[inline]
+  pub fn clear_image(&mut self) {
+    self.image.clear();
+  }
+  /// Writes the printed image as a binary PGM file.
+  pub fn write_pgm(&self, mut w: impl Write) -> io::Result<()> {
+    write!(w, "P5\n{} {}\n255\n", Self::PRINT_WIDTH, self.image_height())?;
+    let gray: Vec<u8> = self.image.iter().map(|&shade| 0xFF - shade * 0x55).collect();
+    w.write_all(&gray)
+  }
+
+  /// The answer to the next byte, which depends on how far into the packet
+  /// we are.
+  fn next_byte_out(&self) -> u8 {
+    match self.packet.len().checked_sub(self.data_len() + 8) {
+      Some(0) => 0x81,
+      Some(1) => self.status,
+      _ => 0x00,
+    }
+  }
+
+  #[inline]
+  fn data_len(&self) -> usize {
+    match self.packet.get(4..6) {
+      Some(&[low, high]) => usize::from(u16::from_le_bytes([low, high])),
+      _ => 0,
+    }
+  }
+
+  fn receive_byte(&mut self, byte: u8) {
+    // throw away garbage until a packet starts.
+    let magic = [0x88, 0x33];
+    if self.packet.len() < magic.len() && byte != magic[self.packet.len()] {
+      self.packet.clear();
+      return;
+    }
+    self.packet.push(byte);
+    let data_len = self.data_len();
+    if self.packet.len() == 6 + data_len + 2 {
+      self.run_packet();
+    } else if self.packet.len() == 6 + data_len + 4 {
+      self.packet.clear();
+    }
+  }
+
+  fn run_packet(&mut self) {
+    let data_len = self.data_len();
+    let body = &self.packet[2..6 + data_len];
+    let sum = body.iter().fold(0_u16, |sum, &b| sum.wrapping_add(u16::from(b)));
+    let checksum = u16::from_le_bytes([self.packet[6 + data_len], self.packet[7 + data_len]]);
+    if sum != checksum {
+      self.status |= Self::STATUS_CHECKSUM_ERROR;
+      return;
+    }
+    self.status &= !Self::STATUS_CHECKSUM_ERROR;
+    let compressed = self.packet[3] & 1 != 0;
+    let data = self.packet[6..6 + data_len].to_vec();
+    match self.packet[2] {
+      Self::CMD_INIT => {
+        self.tile_data.clear();
+        self.busy_cycles = 0;
+        self.status = 0;
+      }
+      Self::CMD_DATA => {
+        if compressed {
+          decompress_into(&data, &mut self.tile_data);
+        } else {
+          self.tile_data.extend_from_slice(&data);
+        }
+        self.tile_data.truncate(Self::BUFFER_SIZE);
+      }
+      Self::CMD_PRINT => {
+        // sheets, margins, palette, exposure
+        let palette = match data.get(2) {
+          // zero means the normal palette.
+          Some(0) | None => 0xE4,
+          Some(&palette) => palette,
+        };
+        self.print(palette);
+      }
+      Self::CMD_STATUS => (),
+      _ => (),
+    }
+    self.update_status();
+  }
+
+  /// Moves all the waiting tile data onto the printed image.
+  fn print(&mut self, palette: u8) {
+    let tiles = self.tile_data.len() / 16;
+    let tile_rows = tiles.div_ceil(20);
+    let top = self.image.len();
+    self.image.resize(top + tile_rows * 8 * Self::PRINT_WIDTH, 0);
+    for (i, tile) in self.tile_data.chunks_exact(16).enumerate() {
+      let (tile_x, tile_y) = (i % 20, i / 20);
+      for (y, row) in tile.chunks_exact(2).enumerate() {
+        for x in 0..8 {
+          let color = row_color(row[0], row[1], x as u8);
+          let pixel = (tile_y * 8 + y) * Self::PRINT_WIDTH + tile_x * 8 + x;
+          self.image[top + pixel] = palette_shade(palette, color);
+        }
+      }
+    }
+    self.tile_data.clear();
+    self.busy_cycles = Self::PRINT_TIME;
+  }
+
+  fn update_status(&mut self) {
+    let mut status = self.status & Self::STATUS_CHECKSUM_ERROR;
+    if self.busy_cycles > 0 {
+      status |= Self::STATUS_PRINTING;
+    }
+    if self.tile_data.len() >= Self::BUFFER_SIZE {
+      status |= Self::STATUS_DATA_FULL;
+    }
+    if !self.tile_data.is_empty() {
+      status |= Self::STATUS_UNPROCESSED_DATA;
+    }
+    self.status = status;
+  }
+}
+impl SerialDevice for GbPrinter {
+  #[inline]
+  fn exchange_bit(&mut self, bit_out: bool) -> bool {
+    if self.bits == 0 {
+      self.byte_out = self.next_byte_out();
+    }
+    let bit_in = self.byte_out & 0x80 != 0;
+    self.byte_out <<= 1;
+    self.byte_in = (self.byte_in << 1) | u8::from(bit_out);
+    self.bits += 1;
+    if self.bits == 8 {
+      self.bits = 0;
+      self.receive_byte(self.byte_in);
+    }
+    bit_in
+  }
+  #[inline]
+  fn m_cycle(&mut self, _transfer: bool) {
+    if self.busy_cycles > 0 {
+      self.busy_cycles -= 1;
+      if self.busy_cycles == 0 {
+        self.update_status();
+      }
+    }
+  }
+}
+
+/// Expands the printer's run length encoding.
+///
+/// A control byte with bit 7 set is followed by one byte to repeat
+/// `(control & 0x7F) + 2` times, otherwise it's followed by `control + 1`
+/// bytes to copy as is.
+fn decompress_into(mut data: &[u8], out: &mut Vec<u8>) {
+  while let [control, rest @ ..] = data {
+    if control & 0x80 != 0 {
+      let Some((&byte, rest)) = rest.split_first() else { break };
+      out.extend(core::iter::repeat_n(byte, usize::from(control & 0x7F) + 2));
+      data = rest;
+    } else {
+      let len = (usize::from(*control) + 1).min(rest.len());
+      out.extend_from_slice(&rest[..len]);
+      data = &rest[len..];
+    }
+  }
+}
+
+#[test]
+fn test_printer_packets() {
+  fn send_packet(printer: &mut GbPrinter, command: u8, compressed: bool, data: &[u8]) -> [u8; 2] {
+    let mut body = vec![command, u8::from(compressed)];
+    body.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    body.extend_from_slice(data);
+    let sum = body.iter().fold(0_u16, |sum, &b| sum.wrapping_add(u16::from(b)));
+    let mut packet = vec![0x88, 0x33];
+    packet.extend_from_slice(&body);
+    packet.extend_from_slice(&sum.to_le_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    let answers: Vec<u8> = packet
+      .iter()
+      .map(|&byte| {
+        (0..8).rev().fold(0, |answer, bit| {
+          (answer << 1) | u8::from(printer.exchange_bit(byte & (1 << bit) != 0))
+        })
+      })
+      .collect();
+    [answers[answers.len() - 2], answers[answers.len() - 1]]
+  }
+
+  let mut printer = GbPrinter::default();
+  assert_eq!(send_packet(&mut printer, GbPrinter::CMD_INIT, false, &[]), [0x81, 0x00]);
+  // one band of tiles, every pixel color 3 except the very first tile's top row
+  // which is color 1, with the repeated part compressed.
+  let mut data = vec![0x01, 0xFF, 0x00];
+  for _ in 0..(GbPrinter::BAND_SIZE - 2) / 129 {
+    data.extend_from_slice(&[0xFF, 0xFF]);
+  }
+  data.extend_from_slice(&[0x80 | ((GbPrinter::BAND_SIZE - 2) % 129 - 2) as u8, 0xFF]);
+  let status = send_packet(&mut printer, GbPrinter::CMD_DATA, true, &data)[1];
+  assert_eq!(status, GbPrinter::STATUS_UNPROCESSED_DATA);
+  let status = send_packet(&mut printer, GbPrinter::CMD_PRINT, false, &[1, 0, 0xE4, 0x40])[1];
+  assert_eq!(status, GbPrinter::STATUS_PRINTING);
+  assert_eq!(printer.image_height(), 16);
+  assert_eq!(&printer.image()[..8], &[1; 8]);
+  assert_eq!(printer.image()[8], 3);
+  assert_eq!(printer.image()[GbPrinter::PRINT_WIDTH], 3);
+  // still printing until the job is done.
+  let status = send_packet(&mut printer, GbPrinter::CMD_STATUS, true, &[])[1];
+  assert_eq!(status, GbPrinter::STATUS_PRINTING);
+}
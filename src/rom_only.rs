@@ -0,0 +1,90 @@
+use crate::{
+  cart_header::{CartHeader, CartType},
+  cpu::CpuView,
+  spare_parts::{ROM_BANK_SIZE, SRAM_BANK_SIZE},
+};
+
+/// A cart with no mapper: 32k of ROM, and maybe 8k of RAM.
+pub struct RomOnly {
+  rom: Box<[u8; ROM_BANK_SIZE * 2]>,
+  ram: Option<Box<[u8; SRAM_BANK_SIZE]>>,
+}
+impl RomOnly {
+  pub fn new(raw_rom: &[u8], raw_ram: Option<&[u8]>) -> Result<Self, ()> {
+    let header = CartHeader::parse(raw_rom)?;
+    // check for correct cart type, which also says if there's ram.
+    let has_ram = match header.cart_type {
+      CartType::RomOnly => false,
+      CartType::RomRam | CartType::RomRamBattery => true,
+      _ => return Err(()),
+    };
+    // without a mapper there's no way to see more than 2 banks.
+    if raw_rom.len() > ROM_BANK_SIZE * 2 {
+      return Err(());
+    }
+    // copy input data
+    let mut rom: Box<[u8; ROM_BANK_SIZE * 2]> = bytemuck::allocation::zeroed_box();
+    rom[..raw_rom.len()].copy_from_slice(raw_rom);
+    let ram = if has_ram {
+      let mut ram: Box<[u8; SRAM_BANK_SIZE]> = bytemuck::allocation::zeroed_box();
+      let raw_ram = raw_ram.unwrap_or(&[]);
+      let len = raw_ram.len().min(SRAM_BANK_SIZE);
+      ram[..len].copy_from_slice(&raw_ram[..len]);
+      Some(ram)
+    } else {
+      None
+    };
+    // complete
+    Ok(Self { rom, ram })
+  }
+}
+impl CpuView for RomOnly {
+  #[inline]
+  fn read(&self, address: u16) -> u8 {
+    match address {
+      0x0000..=0x7FFF => self.rom[usize::from(address)],
+      0xA000..=0xBFFF => match &self.ram {
+        Some(ram) => ram[usize::from(address) - 0xA000],
+        None => 0xFF,
+      },
+      _ => 0xFF,
+    }
+  }
+  #[inline]
+  fn write(&mut self, address: u16, byte: u8) {
+    match address {
+      0xA000..=0xBFFF => {
+        if let Some(ram) = &mut self.ram {
+          ram[usize::from(address) - 0xA000] = byte;
+        }
+      }
+      _ => (),
+    }
+  }
+}
+
+#[test]
+fn test_rom_only() {
+  let mut raw_rom = vec![0_u8; ROM_BANK_SIZE * 2];
+  raw_rom[0x7FFF] = 0x12;
+
+  // no ram, so the ram area is open bus.
+  let mut cart = RomOnly::new(&raw_rom, Some(&[0x34])).unwrap();
+  assert_eq!(cart.read(0x7FFF), 0x12);
+  cart.write(0xA000, 0x56);
+  assert_eq!(cart.read(0xA000), 0xFF);
+
+  for cart_type in [0x08, 0x09] {
+    raw_rom[0x147] = cart_type;
+    let mut cart = RomOnly::new(&raw_rom, Some(&[0x34])).unwrap();
+    assert_eq!(cart.read(0xA000), 0x34);
+    cart.write(0xBFFF, 0x56);
+    assert_eq!(cart.read(0xBFFF), 0x56);
+  }
+
+  raw_rom[0x147] = 0x01;
+  assert!(RomOnly::new(&raw_rom, None).is_err());
+  raw_rom[0x147] = 0x00;
+  raw_rom.resize(ROM_BANK_SIZE * 4, 0);
+  assert!(RomOnly::new(&raw_rom, None).is_err());
+}
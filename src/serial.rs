@@ -0,0 +1,79 @@
+/// Something plugged into the link port.
+///
+/// Data moves one bit at a time, most significant bit first. On every clock
+/// edge the Game Boy shifts one bit out of `SB` and one bit in from the device.
+pub trait SerialDevice: core::fmt::Debug {
+  /// Exchanges one bit on a clock edge from the Game Boy (when `SC` selects
+  /// the internal clock). Gets the bit being sent, and gives back the bit being
+  /// received.
+  fn exchange_bit(&mut self, bit_out: bool) -> bool;
+
+  /// Polled every M-cycle while the Game Boy waits on an external clock.
+  ///
+  /// If the device provides a clock edge now, it takes the bit being sent and
+  /// gives back the bit being received. By default devices don't provide a
+  /// clock, so external clock transfers never finish.
+  fn external_clock(&mut self, bit_out: bool) -> Option<bool> {
+    let _ = bit_out;
+    None
+  }
+
+  /// Called every M-cycle before any bits move, for devices that need to keep
+  /// time. Gets if `SC` has a transfer going (in either clock mode).
+  fn m_cycle(&mut self, transfer: bool) {
+    let _ = transfer;
+  }
+
+  /// The bytes that have been sent to this device, if it keeps them.
+  fn log(&self) -> &[u8] {
+    &[]
+  }
+}
+
+/// Nothing plugged in, so incoming bits are all 1s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Disconnected;
+impl SerialDevice for Disconnected {
+  #[inline]
+  fn exchange_bit(&mut self, _bit_out: bool) -> bool {
+    true
+  }
+}
+
+/// Sends every bit right back, as if the cable's ends were connected to each
+/// other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Loopback;
+impl SerialDevice for Loopback {
+  #[inline]
+  fn exchange_bit(&mut self, bit_out: bool) -> bool {
+    bit_out
+  }
+}
+
+/// Keeps every byte sent, otherwise acting like nothing is plugged in.
+///
+/// Test ROMs commonly print their results over the link port.
+#[derive(Debug, Clone, Default)]
+pub struct SerialLogger {
+  log: Vec<u8>,
+  /// The byte being shifted in right now.
+  byte: u8,
+  bits: u8,
+}
+impl SerialDevice for SerialLogger {
+  #[inline]
+  fn exchange_bit(&mut self, bit_out: bool) -> bool {
+    self.byte = (self.byte << 1) | u8::from(bit_out);
+    self.bits += 1;
+    if self.bits == 8 {
+      self.log.push(self.byte);
+      self.bits = 0;
+    }
+    true
+  }
+  #[inline]
+  fn log(&self) -> &[u8] {
+    &self.log
+  }
+}
@@ -3,8 +3,9 @@ use core::ops::Range;
 use crate::{
   button_state::ButtonState,
   cpu::CpuView,
-  mmio::{SerialControl, TimerControl, MMIO},
-  ppu::OamEntry,
+  mmio::{Model, SerialControl, TimerControl, MMIO},
+  ppu::{OamEntry, PpuMode},
+  serial::SerialDevice,
 };
 
 pub const ROM_BANK_SIZE: usize = 16 * 1024;
@@ -24,8 +25,15 @@ pub struct SpareParts {
   wram_bank: usize,
   /// Object Attributes
   oam: [OamEntry; 40],
+  /// The row of OAM the PPU is reading during its scan, if it's scanning.
+  oam_scan_row: Option<usize>,
   /// IO controls and high page ram
   mmio: MMIO,
+  /// The OAM DMA currently copying bytes, if any.
+  dma: Option<OamDma>,
+  /// A newly requested OAM DMA, which takes over from any current one once it
+  /// gets going.
+  dma_starting: Option<OamDma>,
 }
 impl SpareParts {
   pub fn from_cart(cart: Box<dyn CpuView>) -> Self {
@@ -36,15 +44,26 @@ impl SpareParts {
       wram: bytemuck::allocation::zeroed_box(),
       wram_bank: 1,
       oam: [Default::default(); 40],
+      oam_scan_row: None,
       mmio: Default::default(),
+      dma: None,
+      dma_starting: None,
     }
   }
 
+  #[inline]
+  pub fn set_model(&mut self, model: Model) {
+    self.mmio.set_model(model);
+  }
   #[inline]
   pub fn set_button_state(&mut self, button_state: ButtonState) {
     self.mmio.set_button_state(button_state);
   }
   #[inline]
+  pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+    self.mmio.set_serial_device(device)
+  }
+  #[inline]
   pub fn set_serial_logging(&mut self, log: bool) {
     self.mmio.set_serial_logging(log);
   }
@@ -53,15 +72,107 @@ impl SpareParts {
     self.mmio.serial_log()
   }
 
+  #[inline]
+  pub fn set_oam_scan_row(&mut self, row: Option<usize>) {
+    self.oam_scan_row = row;
+  }
+
   #[inline]
   pub fn m_cycle(&mut self) {
     self.mmio.m_cycle();
+    self.cart.m_cycle();
+    self.dma_m_cycle();
+  }
+
+  /// Starts (or restarts) an OAM DMA from the given page.
+  #[inline]
+  fn start_dma(&mut self, page: u8) {
+    // there's one M-cycle of setup after the write before copying starts.
+    let source = u16::from(page) << 8;
+    self.dma_starting = Some(OamDma { source, index: 0, delay: 1, byte: 0xFF });
+  }
+  #[inline]
+  fn dma_m_cycle(&mut self) {
+    if let Some(starting) = &mut self.dma_starting {
+      if starting.delay > 0 {
+        starting.delay -= 1;
+      } else {
+        self.dma = self.dma_starting.take();
+      }
+    }
+    if let Some(mut dma) = self.dma {
+      dma.byte = self.dma_source_read(dma.source + dma.index);
+      bytemuck::bytes_of_mut(&mut self.oam)[usize::from(dma.index)] = dma.byte;
+      dma.index += 1;
+      self.dma = if dma.index < OamDma::LEN { Some(dma) } else { None };
+    }
+  }
+  /// Reads a byte for an OAM DMA, which doesn't go through the normal CPU
+  /// restrictions.
+  #[inline]
+  fn dma_source_read(&self, address: u16) -> u8 {
+    match address {
+      0x0000..=0x7FFF => self.cart.read(address),
+      0x8000..=0x9FFF => {
+        let address = usize::from(address - 0x8000);
+        self.vram[self.vram_bank][address]
+      }
+      0xA000..=0xBFFF => self.cart.read(address),
+      // sources past WRAM just see WRAM again.
+      0xC000..=0xFFFF => {
+        let address = usize::from(address & 0x1FFF);
+        if address < WRAM_BANK_SIZE {
+          self.wram[0][address]
+        } else {
+          self.wram[self.wram_bank][address - WRAM_BANK_SIZE]
+        }
+      }
+    }
+  }
+
+  /// On the DMG, putting an OAM address on the bus while the PPU is scanning
+  /// OAM garbles the row of OAM that the PPU is reading, based on the row
+  /// before it.
+  fn corrupt_oam(&mut self, address: u16, read: bool) {
+    if self.mmio.model() != Model::Dmg || !(0xFE00..=0xFEFF).contains(&address) {
+      return;
+    }
+    // the first row never gets corrupted.
+    let Some(row @ 1..) = self.oam_scan_row else { return };
+    let oam = bytemuck::bytes_of_mut(&mut self.oam);
+    let word = |oam: &[u8], i: usize| u16::from_le_bytes([oam[i], oam[i + 1]]);
+    let (this, prev) = (row * 8, (row - 1) * 8);
+    let a = word(oam, this);
+    let b = word(oam, prev);
+    let c = word(oam, prev + 4);
+    let new_a = if read { b | (a & c) } else { ((a ^ c) & (b ^ c)) ^ c };
+    oam[this..this + 2].copy_from_slice(&new_a.to_le_bytes());
+    oam.copy_within(prev + 2..prev + 8, this + 2);
+  }
+
+  /// The CPU can't access VRAM while the PPU is drawing.
+  #[inline]
+  fn vram_locked(&self) -> bool {
+    self.mmio.stat().mode() == PpuMode::Draw
+  }
+  /// The CPU can't access OAM while the PPU is scanning OAM or drawing.
+  #[inline]
+  fn oam_locked(&self) -> bool {
+    matches!(self.mmio.stat().mode(), PpuMode::Scan | PpuMode::Draw)
   }
   #[inline]
   pub fn mmio(&self) -> &MMIO {
     &self.mmio
   }
   #[inline]
+  pub fn vram(&self) -> &[[u8; VRAM_BANK_SIZE]; 2] {
+    &self.vram
+  }
+  #[inline]
+  pub fn oam(&self) -> &[OamEntry; 40] {
+    &self.oam
+  }
+  #[inline]
   pub fn mmio_mut(&mut self) -> &mut MMIO {
     &mut self.mmio
   }
@@ -70,10 +181,18 @@ impl CpuView for SpareParts {
   #[inline]
   #[must_use]
   fn read(&self, address: u16) -> u8 {
-    // TODO: simulate some regions being locked out when DMA is active.
+    // during OAM DMA the CPU only has access to the IO registers and HRAM, and
+    // other reads see whatever the DMA is moving around.
+    if let Some(dma) = &self.dma {
+      match address {
+        0xFE00..=0xFEFF => return 0xFF,
+        0xFF00..=0xFFFF => (),
+        _ => return dma.byte,
+      }
+    }
     match address {
       0x0000..=0x7FFF => self.cart.read(address),
-      0x8000..=0x9FFF => {
+      0x8000..=0x9FFF if !self.vram_locked() => {
         let address = usize::from(address - 0x8000);
         self.vram[self.vram_bank][address]
       }
@@ -86,24 +205,46 @@ impl CpuView for SpareParts {
         let address = usize::from(address - 0xD000);
         self.wram[self.wram_bank][address]
       }
-      0xFE00..=0xFE9F => {
+      // echo ram mirrors wram
+      0xE000..=0xEFFF => {
         let address = usize::from(address - 0xE000);
+        self.wram[0][address]
+      }
+      0xF000..=0xFDFF => {
+        let address = usize::from(address - 0xF000);
+        self.wram[self.wram_bank][address]
+      }
+      0xFE00..=0xFE9F if !self.oam_locked() => {
+        let address = usize::from(address - 0xFE00);
         bytemuck::bytes_of(&self.oam)[address]
       }
+      // the unused area after OAM reads as 0 on the DMG, and as the upper
+      // nibble of the address repeated on the CGB.
+      0xFEA0..=0xFEFF if !self.oam_locked() => match self.mmio.model() {
+        Model::Dmg => 0x00,
+        Model::Cgb => {
+          let nibble = (address as u8) & 0xF0;
+          nibble | (nibble >> 4)
+        }
+      },
       0xFF00..=0xFFFF => self.mmio.read(address as u8),
-      // "illegal" location reads just see 0xFF
+      // "illegal" location reads (and locked out VRAM/OAM reads) just see 0xFF
       _ => 0xFF,
     }
   }
 
   #[inline]
   fn write(&mut self, address: u16, byte: u8) {
-    // TODO: simulate some regions being locked out when DMA is active.
+    // during OAM DMA the CPU only has access to the IO registers and HRAM.
+    if self.dma.is_some() && address < 0xFF00 {
+      return;
+    }
+    self.corrupt_oam(address, false);
     match address {
       // rom
       0x0000..=0x7FFF => self.cart.write(address, byte),
       // vram
-      0x8000..=0x9FFF => {
+      0x8000..=0x9FFF if !self.vram_locked() => {
         let address = usize::from(address - 0x8000);
         self.vram[self.vram_bank][address] = byte;
       }
@@ -118,17 +259,64 @@ impl CpuView for SpareParts {
         let address = usize::from(address - 0xD000);
         self.wram[self.wram_bank][address] = byte;
       }
-      // oam
-      0xFE00..=0xFE9F => {
+      // echo ram
+      0xE000..=0xEFFF => {
         let address = usize::from(address - 0xE000);
+        self.wram[0][address] = byte;
+      }
+      0xF000..=0xFDFF => {
+        let address = usize::from(address - 0xF000);
+        self.wram[self.wram_bank][address] = byte;
+      }
+      // oam
+      0xFE00..=0xFE9F if !self.oam_locked() => {
+        let address = usize::from(address - 0xFE00);
         bytemuck::bytes_of_mut(&mut self.oam)[address] = byte;
       }
+      // oam dma
+      0xFF46 => {
+        self.mmio.write(address as u8, byte);
+        self.start_dma(byte);
+      }
       // mmio controls and hram
       0xFF00..=0xFFFF => self.mmio.write(address as u8, byte),
-      // "illegal" location writes will just do nothing.
+      // "illegal" location writes (and locked out VRAM/OAM writes) will just do
+      // nothing.
       _ => (),
     }
   }
+
+  #[inline]
+  fn after_read(&mut self, address: u16) {
+    if self.dma.is_none() {
+      self.corrupt_oam(address, true);
+    }
+  }
+  #[inline]
+  fn idu_address(&mut self, address: u16) {
+    if self.dma.is_none() {
+      self.corrupt_oam(address, false);
+    }
+  }
+  #[inline]
+  fn rumble(&self) -> bool {
+    self.cart.rumble()
+  }
+}
+
+/// Copies a page of memory into OAM, one byte per M-cycle.
+#[derive(Debug, Clone, Copy)]
+struct OamDma {
+  source: u16,
+  index: u16,
+  /// M-cycles of setup left before copying starts.
+  delay: u8,
+  /// The byte most recently copied.
+  byte: u8,
+}
+impl OamDma {
+  /// The full size of OAM gets copied.
+  const LEN: u16 = 160;
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -140,3 +328,106 @@ pub enum IrqTy {
   Serial = 3,
   Joypad = 4,
 }
+
+#[test]
+fn test_vram_oam_lock() {
+  let mut parts = SpareParts::from_cart(Box::new(vec![0_u8; 0x8000]));
+  parts.mmio_mut().set_ppu_mode(PpuMode::HBlank);
+  parts.write(0x8000, 0x12);
+  parts.write(0xFE00, 0x34);
+
+  for (mode, vram, oam) in [
+    (PpuMode::HBlank, 0x12, 0x34),
+    (PpuMode::VBlank, 0x12, 0x34),
+    (PpuMode::Scan, 0x12, 0xFF),
+    (PpuMode::Draw, 0xFF, 0xFF),
+  ] {
+    parts.mmio_mut().set_ppu_mode(mode);
+    assert_eq!(parts.read(0x8000), vram, "{mode:?}");
+    assert_eq!(parts.read(0xFE00), oam, "{mode:?}");
+    // locked out writes are lost.
+    parts.write(0x8000, 0x56);
+    parts.write(0xFE00, 0x78);
+    parts.mmio_mut().set_ppu_mode(PpuMode::HBlank);
+    assert_eq!(parts.read(0x8000), if vram == 0xFF { 0x12 } else { 0x56 });
+    assert_eq!(parts.read(0xFE00), if oam == 0xFF { 0x34 } else { 0x78 });
+    parts.write(0x8000, 0x12);
+    parts.write(0xFE00, 0x34);
+  }
+}
+
+#[test]
+fn test_oam_dma() {
+  let mut parts = SpareParts::from_cart(Box::new(vec![0_u8; 0x8000]));
+  parts.mmio_mut().set_ppu_mode(PpuMode::HBlank);
+  for i in 0..0xA0 {
+    parts.write(0xC000 + i, i as u8 + 1);
+    parts.write(0xD000 + i, !(i as u8));
+  }
+  parts.write(0xFF80, 0x99);
+  let oam = |parts: &SpareParts, i: usize| bytemuck::bytes_of(parts.oam())[i];
+
+  parts.write(0xFF46, 0xC0);
+  // one M-cycle of setup where the CPU can still use the bus.
+  parts.m_cycle();
+  assert_eq!(parts.read(0xC010), 0x11);
+  assert_eq!(oam(&parts, 0), 0);
+
+  parts.m_cycle();
+  assert_eq!(oam(&parts, 0), 0x01);
+  // outside of IO and HRAM the CPU sees the byte being copied.
+  assert_eq!(parts.read(0xC010), 0x01);
+  assert_eq!(parts.read(0x0000), 0x01);
+  assert_eq!(parts.read(0xFE00), 0xFF);
+  assert_eq!(parts.read(0xFF80), 0x99);
+  parts.write(0xC010, 0x55);
+  parts.write(0xFF81, 0x66);
+  assert_eq!(parts.read(0xFF81), 0x66);
+
+  for _ in 1..50 {
+    parts.m_cycle();
+  }
+  assert_eq!(oam(&parts, 49), 50);
+  // a restart keeps the old copy going through its setup.
+  parts.write(0xFF46, 0xD0);
+  parts.m_cycle();
+  assert_eq!(oam(&parts, 50), 51);
+  parts.m_cycle();
+  assert_eq!(oam(&parts, 0), 0xFF);
+  assert_eq!(oam(&parts, 51), 0);
+
+  for _ in 1..0xA0 {
+    parts.m_cycle();
+  }
+  for i in 0..0xA0 {
+    assert_eq!(oam(&parts, i), !(i as u8));
+  }
+  // the transfer is over and the bus is back to normal.
+  parts.m_cycle();
+  assert_eq!(parts.read(0xC010), 0x11);
+  assert_eq!(parts.read(0xFE01), 0xFE);
+}
+
+#[test]
+fn test_echo_ram_and_unusable_area() {
+  let mut parts = SpareParts::from_cart(Box::new(vec![0_u8; 0x8000]));
+  parts.mmio_mut().set_ppu_mode(PpuMode::HBlank);
+  parts.write(0xC123, 0x11);
+  parts.write(0xD456, 0x22);
+  assert_eq!(parts.read(0xE123), 0x11);
+  assert_eq!(parts.read(0xF456), 0x22);
+  parts.write(0xE200, 0x33);
+  parts.write(0xFDFF, 0x44);
+  assert_eq!(parts.read(0xC200), 0x33);
+  assert_eq!(parts.read(0xDDFF), 0x44);
+
+  parts.write(0xFEA5, 0x55);
+  assert_eq!(parts.read(0xFEA5), 0x00);
+  assert_eq!(parts.read(0xFEFF), 0x00);
+  parts.set_model(Model::Cgb);
+  assert_eq!(parts.read(0xFEA5), 0xAA);
+  assert_eq!(parts.read(0xFEFF), 0xFF);
+  // locked out along with OAM.
+  parts.mmio_mut().set_ppu_mode(PpuMode::Scan);
+  assert_eq!(parts.read(0xFEA5), 0xFF);
+}
@@ -5,8 +5,9 @@ use pixel_formats::r8g8b8a8_Srgb;
 use crate::{
   button_state::ButtonState,
   cpu::{CpuMode, CpuView, SM83},
-  mmio::MMIO,
-  ppu::PPU,
+  mmio::{Model, MMIO},
+  ppu::{DmgPalette, PpuMode, PPU},
+  serial::SerialDevice,
   spare_parts::SpareParts,
 };
 
@@ -14,14 +15,22 @@ pub const LCD_WIDTH: u8 = 160;
 pub const LCD_HEIGHT: u8 = 144;
 pub const LCD_PIXEL_COUNT: usize = LCD_WIDTH as usize * LCD_HEIGHT as usize;
 pub type LCD = [r8g8b8a8_Srgb; LCD_PIXEL_COUNT];
+/// The 2-bit shade of each LCD pixel, before being colored by a palette.
+pub type Shades = [u8; LCD_PIXEL_COUNT];
+/// The number of T-cycles (dots) in one full frame.
+pub const DOTS_PER_FRAME: usize = 70224;
 
 pub struct System {
   cpu: SM83,
   cpu_mode: CpuMode,
   ppu: PPU,
   lcd: LCD,
+  shades: Shades,
   parts: SpareParts,
   t_clock: usize,
+  frame_count: usize,
+  /// T-cycles since the last frame was completed.
+  frame_dots: usize,
 }
 impl System {
   #[inline]
@@ -32,12 +41,26 @@ impl System {
       cpu,
       cpu_mode: CpuMode::Normal,
       ppu: PPU::default(),
-      lcd: [r8g8b8a8_Srgb::OPAQUE_BLACK; LCD_PIXEL_COUNT],
+      lcd: [DmgPalette::default().0[0]; LCD_PIXEL_COUNT],
+      shades: [0; LCD_PIXEL_COUNT],
       parts: SpareParts::from_cart(cart),
       t_clock: 0,
+      frame_count: 0,
+      frame_dots: 0,
     }
   }
 
+  /// Picks which kind of Game Boy the IO registers act like.
+  #[inline]
+  pub fn set_model(&mut self, model: Model) {
+    self.parts.set_model(model);
+  }
+
+  /// Plugs a new device into the link port, giving back the old one.
+  #[inline]
+  pub fn set_serial_device(&mut self, device: Box<dyn SerialDevice>) -> Box<dyn SerialDevice> {
+    self.parts.set_serial_device(device)
+  }
   #[inline]
   pub fn set_serial_logging(&mut self, log: bool) {
     self.parts.set_serial_logging(log);
@@ -51,6 +74,29 @@ impl System {
   pub fn lcd(&self) -> &[r8g8b8a8_Srgb; 144 * 160] {
     &self.lcd
   }
+  #[inline]
+  pub fn shades(&self) -> &Shades {
+    &self.shades
+  }
+
+  #[inline]
+  pub fn palette(&self) -> DmgPalette {
+    self.ppu.palette()
+  }
+  /// Changes the palette, including recoloring whatever is on the LCD now.
+  #[inline]
+  pub fn set_palette(&mut self, palette: DmgPalette) {
+    self.ppu.set_palette(palette);
+    for (color, shade) in self.lcd.iter_mut().zip(self.shades.iter()) {
+      *color = palette.0[usize::from(*shade)];
+    }
+  }
+
+  /// If the cart's rumble motor is running, for frontends to poll.
+  #[inline]
+  pub fn rumble(&self) -> bool {
+    self.parts.rumble()
+  }
 
   #[inline]
   pub fn set_button_state(&mut self, button_state: ButtonState) {
@@ -60,10 +106,23 @@ impl System {
   #[inline]
   pub fn t_cycle(&mut self) {
     self.t_clock = self.t_clock.wrapping_add(1);
-    self.ppu.t_cycle(&mut self.lcd);
+    let ppu_mode = self.ppu.t_cycle(&mut self.lcd, &mut self.shades, &self.parts);
+    let mmio = self.parts.mmio_mut();
+    let old_ppu_mode = mmio.stat().mode();
+    mmio.set_ly(self.ppu.current_scanline());
+    mmio.set_ppu_mode(ppu_mode);
+    self.parts.set_oam_scan_row(self.ppu.oam_scan_row());
+    // a frame is done when the PPU enters VBlank, or after a frame's worth of
+    // time if the LCD is off.
+    self.frame_dots += 1;
+    let vblank_start = ppu_mode == PpuMode::VBlank && old_ppu_mode != PpuMode::VBlank;
+    let lcd_off_frame = !self.ppu.lcd_enabled() && self.frame_dots >= DOTS_PER_FRAME;
+    if vblank_start || lcd_off_frame {
+      self.frame_count = self.frame_count.wrapping_add(1);
+      self.frame_dots = 0;
+    }
     //
     if self.t_clock & 0b11 == 0 {
-      self.parts.mmio_mut().set_ly(self.ppu.current_scanline());
       self.parts.m_cycle();
       match self.cpu_mode {
         CpuMode::Normal => self.cpu_mode = self.cpu.m_cycle(&mut self.parts),
@@ -76,7 +135,7 @@ impl System {
         CpuMode::Stopped => {
           // wake when any of the low 4 bits of `JOYP` become zero, regardless
           // of interrupt settings.
-          if self.parts.mmio().joyp() & 0b1111 != 0 {
+          if self.parts.mmio().joyp() & 0b1111 != 0b1111 {
             self.cpu_mode = self.cpu.m_cycle(&mut self.parts)
           }
         }
@@ -92,8 +151,64 @@ impl System {
     self.t_cycle();
   }
 
+  /// Runs the given number of T-cycles.
+  #[inline]
+  pub fn run_cycles(&mut self, t_cycles: usize) {
+    for _ in 0..t_cycles {
+      self.t_cycle();
+    }
+  }
+
+  /// Runs until the current frame is done, after which the LCD holds the
+  /// finished frame.
+  #[inline]
+  pub fn run_frame(&mut self) {
+    let frame_count = self.frame_count;
+    while self.frame_count == frame_count {
+      self.t_cycle();
+    }
+  }
+
+  /// The number of frames completed so far.
+  #[inline]
+  pub fn frame_count(&self) -> usize {
+    self.frame_count
+  }
+
   #[inline]
   pub fn mmio(&self) -> &MMIO {
     self.parts.mmio()
   }
 }
+
+#[test]
+fn test_lcd_off_registers() {
+  let mut rom = vec![0_u8; 0x8000];
+  // wait for LY=50, then turn the LCD off and spin.
+  rom[0x100..0x10C]
+    .copy_from_slice(&[0xF0, 0x44, 0xFE, 50, 0x20, 0xFA, 0x3E, 0x11, 0xE0, 0x40, 0x18, 0xFE]);
+  let mut system = System::from_cart(Box::new(rom));
+  for _ in 0..(2 * 70_224 / 4) {
+    system.m_cycle();
+  }
+  assert!(!system.mmio().lcdc().lcd_enabled());
+  assert_eq!(system.mmio().read(0x44), 0);
+  assert_eq!(system.mmio().read(0x41) & 0b11, 0);
+}
+
+#[test]
+fn test_frame_length() {
+  let mut rom = vec![0_u8; 0x8000];
+  rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+  let mut system = System::from_cart(Box::new(rom));
+  system.run_frame();
+  let start = system.t_clock;
+  let mut last_line = 0;
+  let frame_count = system.frame_count();
+  while system.frame_count() == frame_count {
+    system.t_cycle();
+    last_line = last_line.max(system.mmio().ly());
+  }
+  assert_eq!(system.t_clock - start, DOTS_PER_FRAME);
+  assert_eq!(last_line, 153);
+}